@@ -0,0 +1,207 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::asteroid::AsteroidTiers;
+use crate::sound::Sound;
+
+pub const SHIP_TEXTURE: &str = "img/ship.png";
+pub const MISSILE_TEXTURE: &str = "img/missile.png";
+pub const SAUCER_TEXTURE: &str = "img/saucer.png";
+pub const RING_TEXTURE: &str = "img/ring.png"; //Hyperspace and explosion effect
+pub const FONT_FILENAME: &str = "fonts/Monocraft.ttf";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AssetKind {
+    Texture,
+    Sound,
+    Font
+}
+
+impl AssetKind {
+    // What stands in for an asset of this kind that can't be loaded, if anything can
+    pub fn fallback(self) -> Option<&'static str> {
+        match self {
+            AssetKind::Texture => Some("a placeholder texture"),
+            AssetKind::Sound => Some("silence"),
+            AssetKind::Font => None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AssetKind::Texture => "texture",
+            AssetKind::Sound => "sound",
+            AssetKind::Font => "font"
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Asset {
+    pub path: String,
+    pub kind: AssetKind
+}
+
+#[derive(Clone, Debug)]
+pub struct AssetProblem {
+    pub asset: Asset,
+    pub reason: String
+}
+
+impl AssetProblem {
+    pub fn new(path: &str, kind: AssetKind, reason: String) -> Self {
+        AssetProblem {
+            asset: Asset{path: path.to_string(), kind},
+            reason
+        }
+    }
+
+    // The game can't run without the asset
+    pub fn is_fatal(&self) -> bool {
+        self.asset.kind.fallback().is_none()
+    }
+}
+
+impl fmt::Display for AssetProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.asset.path, self.asset.kind.name(), self.reason)?;
+        if let Some(fallback) = self.asset.kind.fallback() {
+            write!(f, ", using {} instead", fallback)?;
+        }
+        Ok(())
+    }
+}
+
+// Every file the game client loads, with the asteroid sprites from the tier table
+pub fn manifest(tiers: &AsteroidTiers) -> Vec<Asset> {
+    let mut assets = Vec::<Asset>::new();
+    let mut add = |path: &str, kind: AssetKind| {
+        if !assets.iter().any(|asset| asset.path == path) {
+            assets.push(Asset{path: path.to_string(), kind});
+        }
+    };
+
+    for path in [SHIP_TEXTURE, MISSILE_TEXTURE, SAUCER_TEXTURE, RING_TEXTURE] {
+        add(path, AssetKind::Texture);
+    }
+    for sprite in tiers.sprites() {
+        add(sprite, AssetKind::Texture);
+    }
+    for sound in Sound::ALL {
+        add(sound.filename(), AssetKind::Sound);
+    }
+    add(FONT_FILENAME, AssetKind::Font);
+    assets
+}
+
+// Looks for missing, unreadable, empty or mislabelled files, without decoding them
+pub fn check(assets: &[Asset]) -> Vec<AssetProblem> {
+    let mut problems = Vec::new();
+    for asset in assets {
+        let reason = match fs::read(&asset.path) {
+            Ok(bytes) => check_format(&asset.path, &bytes).err(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Some("missing".to_string()),
+            Err(e) => Some(e.to_string())
+        };
+        if let Some(reason) = reason {
+            problems.push(AssetProblem{asset: asset.clone(), reason});
+        }
+    }
+    problems
+}
+
+// The file starts the way its extension says it should
+fn check_format(path: &str, bytes: &[u8]) -> Result<(), String> {
+    if bytes.is_empty() {
+        return Err("empty file".to_string());
+    }
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    let valid = match extension.as_str() {
+        "png" => bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]),
+        "jpg" | "jpeg" => bytes.starts_with(&[0xFF, 0xD8, 0xFF]),
+        "wav" => bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WAVE",
+        "mp3" => bytes.starts_with(b"ID3") || (bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xE0 == 0xE0),
+        "ttf" | "otf" => bytes.starts_with(&[0x00, 0x01, 0x00, 0x00]) || bytes.starts_with(b"OTTO") || bytes.starts_with(b"true"),
+        _ => true
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("not a valid .{} file", extension))
+    }
+}
+
+pub fn has_problem(problems: &[AssetProblem], path: &str) -> bool {
+    problems.iter().any(|problem| problem.asset.path == path)
+}
+
+// Prints every problem at once. Fails, listing them all, if any asset can't be done without.
+pub fn report(problems: &[AssetProblem]) -> Result<(), String> {
+    let list: Vec<String> = problems.iter().map(|problem| format!("  {}", problem)).collect();
+    if problems.iter().any(|problem| problem.is_fatal()) {
+        return Err(format!("Can't start, some game files are missing or broken:\n{}", list.join("\n")));
+    }
+    if !list.is_empty() {
+        println!("Some game files are missing or broken:\n{}", list.join("\n"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes a file into a directory of its own under the temp dir
+    fn temp_file(name: &str, bytes: &[u8]) -> String {
+        let dir = std::env::temp_dir().join("asteroids_assets_test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn asset(path: &str, kind: AssetKind) -> Asset {
+        Asset{path: path.to_string(), kind}
+    }
+
+    #[test]
+    fn missing_file_is_reported() {
+        let problems = check(&[asset("img/no_such_sprite.png", AssetKind::Texture)]);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].reason, "missing");
+        assert!(!problems[0].is_fatal());
+    }
+
+    #[test]
+    fn file_not_matching_its_extension_is_reported() {
+        let path = temp_file("really_a_png.wav", &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0]);
+        let problems = check(&[asset(&path, AssetKind::Sound)]);
+        fs::remove_file(&path).ok();
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].reason, "not a valid .wav file");
+    }
+
+    #[test]
+    fn every_problem_is_listed() {
+        let good = temp_file("good.png", &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+        let empty = temp_file("empty.mp3", &[]);
+        let assets = [
+            asset("img/no_such_sprite.png", AssetKind::Texture),
+            asset(&good, AssetKind::Texture),
+            asset(&empty, AssetKind::Sound),
+            asset("fonts/no_such_font.ttf", AssetKind::Font)
+        ];
+        let problems = check(&assets);
+        fs::remove_file(&good).ok();
+        fs::remove_file(&empty).ok();
+
+        assert_eq!(problems.len(), 3);
+        assert!(!has_problem(&problems, &good));
+        //The missing font is fatal, but the others are still in the message
+        let error = report(&problems).unwrap_err();
+        assert!(error.contains("img/no_such_sprite.png (texture): missing, using a placeholder texture instead"), "{}", error);
+        assert!(error.contains("empty.mp3 (sound): empty file, using silence instead"), "{}", error);
+        assert!(error.contains("fonts/no_such_font.ttf (font): missing"), "{}", error);
+    }
+}
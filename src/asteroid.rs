@@ -1,90 +1,409 @@
-use specs::{System, WriteStorage, Join, Read};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use specs::{System, SystemData, WriteStorage, ReadStorage, Join, Read, World, ReaderId};
 use specs::prelude::Entities;
 
-pub struct AsteroidMover;
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
 
-use crate::{components, NO_OF_SECTIONS, SECTION_HEIGHT, SECTION_WIDTH};
+pub struct WallBouncer;
 
-impl<'a> System<'a> for AsteroidMover{
-    type SystemData = (
-        WriteStorage<'a, components::Position>,
-        WriteStorage<'a, components::Renderable>,
-        WriteStorage<'a, components::Asteroid>,
-        Read<'a,crate::DeltaTime>
-    );
+use crate::{components, collision, game, AsteroidCollisions, GameMode};
+use crate::collision::{CollisionEvent, CollisionEvents};
 
-    fn run(&mut self, mut data: Self::SystemData) {
-        let deltatime = data.3.0;
-        for(pos,rend,asteroid) in (&mut data.0, &mut data.1, &data.2).join(){
-            let radians = pos.rot.to_radians();
+pub const ASTEROIDS_FILENAME: &str = "asteroids.toml";
 
-            pos.x += asteroid.speed * radians.sin() * deltatime;
-            pos.y -= asteroid.speed * radians.cos() * deltatime;
+// One type of asteroid from the tier table, see asteroids.toml for what each field does
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AsteroidTier {
+    #[serde(skip)]
+    pub name: String,
+    pub sprite: String,
+    pub size: u32,
+    pub radius: f64,
+    pub speed: (f64, f64),
+    pub spin: (f64, f64),
+    #[serde(default = "default_hit_points")]
+    pub hit_points: u32,
+    pub points: u32,
+    #[serde(default)]
+    pub splits_into: Vec<String>,
+    #[serde(skip)]
+    pub splits: Vec<usize>, //splits_into looked up in the table
+    #[serde(default)]
+    pub spawn_weight: u32,
+    #[serde(default)]
+    pub blast_radius: f64
+}
+
+fn default_hit_points() -> u32 {
+    1
+}
 
-            pos.section = (pos.x as u32/ SECTION_WIDTH) * NO_OF_SECTIONS +  (pos.y as u32 / SECTION_HEIGHT);
+// Every asteroid type, in name order. Asteroids refer to their type by its index.
+#[derive(Clone, Debug)]
+pub struct AsteroidTiers {
+    tiers: Vec<AsteroidTier>
+}
 
-            let half_width = (rend.o_w / 2) as u32;
-            let half_height = (rend.o_h / 2) as u32;
+impl AsteroidTiers {
+    // Parses and checks a table of [name] sections, reporting every problem at once
+    pub fn from_toml(contents: &str) -> Result<AsteroidTiers, String> {
+        let table: BTreeMap<String, AsteroidTier> = toml::from_str(contents).map_err(|e| e.to_string())?;
+        let names: Vec<String> = table.keys().cloned().collect();
+        let mut tiers = Vec::new();
+        let mut errors = Vec::new();
 
-            if pos.x > (crate::SCREEN_WIDTH - half_width).into()
-                || pos.x < half_width.into(){
-                    pos.rot = 360.0 - pos.rot;
-            } else if pos.y > (crate::SCREEN_HEIGHT - half_height).into()
-                || pos.y < half_height.into(){
-                    if pos.rot > 180.0 {
-                        pos.rot = 540.0 - pos.rot;
-                    } else {
-                        pos.rot = 180.0 - pos.rot;
-                    }
+        for (name, mut tier) in table {
+            tier.name = name.clone();
+            if tier.sprite.is_empty() {
+                errors.push(format!("[{}] has no sprite", name));
+            }
+            if tier.size == 0 || tier.radius <= 0.0 {
+                errors.push(format!("[{}] size and radius must be above 0", name));
+            }
+            if tier.speed.0 < 0.0 || tier.speed.0 > tier.speed.1 {
+                errors.push(format!("[{}] speed must be [min, max] with 0 <= min <= max", name));
+            }
+            if tier.spin.0 > tier.spin.1 {
+                errors.push(format!("[{}] spin must be [min, max] with min <= max", name));
             }
+            if tier.hit_points == 0 {
+                errors.push(format!("[{}] hit_points must be at least 1", name));
+            }
+            if tier.blast_radius < 0.0 {
+                errors.push(format!("[{}] blast_radius can't be negative", name));
+            }
+            for split in &tier.splits_into {
+                match names.iter().position(|other| other == split) {
+                    Some(index) => tier.splits.push(index),
+                    None => errors.push(format!("[{}] splits into unknown type \"{}\"", name, split))
+                }
+            }
+            tiers.push(tier);
+        }
 
-            rend.rot += asteroid.rot_speed * deltatime;
-            if rend.rot > 360.0 {
-                rend.rot -= 360.0;
+        if !tiers.iter().any(|tier| tier.spawn_weight > 0) {
+            errors.push("no asteroid type has a spawn_weight, waves would be empty".to_string());
+        }
+        let tiers = AsteroidTiers{tiers};
+        if errors.is_empty() {
+            if let Some(name) = tiers.split_loop() {
+                errors.push(format!("[{}] splits back into itself, its pieces would never run out", name));
             }
-            if rend.rot < 0.0 {
-                rend.rot += 360.0;
+        }
+
+        if errors.is_empty() {
+            Ok(tiers)
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    // Loads the tier file if there is one, otherwise uses the built in table
+    pub fn load(path: &str) -> Result<AsteroidTiers, String> {
+        if !Path::new(path).exists() {
+            return Ok(AsteroidTiers::default());
+        }
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        AsteroidTiers::from_toml(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&AsteroidTier> {
+        self.tiers.get(index)
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.tiers.iter().position(|tier| tier.name == name)
+    }
+
+    // A type to start a wave with, weighted by spawn_weight
+    pub fn pick_spawn(&self, rng: &mut StdRng) -> usize {
+        let total: u32 = self.tiers.iter().map(|tier| tier.spawn_weight).sum();
+        let mut roll = rng.gen_range(0..total);
+        for (index, tier) in self.tiers.iter().enumerate() {
+            if roll < tier.spawn_weight {
+                return index;
             }
+            roll -= tier.spawn_weight;
+        }
+        0
+    }
 
+    // The type drawn smallest, for filling the screen with asteroids
+    pub fn smallest(&self) -> usize {
+        (0..self.tiers.len()).min_by_key(|index| self.tiers[*index].size).unwrap_or(0)
+    }
+
+    pub fn sprites(&self) -> Vec<&str> {
+        let mut sprites: Vec<&str> = self.tiers.iter().map(|tier| tier.sprite.as_str()).collect();
+        sprites.sort();
+        sprites.dedup();
+        sprites
+    }
+
+    // Name of a type whose pieces eventually split back into it, if any
+    fn split_loop(&self) -> Option<String> {
+        for (start, tier) in self.tiers.iter().enumerate() {
+            let mut visited = vec![false; self.tiers.len()];
+            let mut stack = tier.splits.clone();
+            while let Some(index) = stack.pop() {
+                if index == start {
+                    return Some(tier.name.clone());
+                }
+                if !visited[index] {
+                    visited[index] = true;
+                    stack.extend(&self.tiers[index].splits);
+                }
+            }
         }
+        None
+    }
+}
+
+impl Default for AsteroidTiers {
+    // The table shipped with the game
+    fn default() -> Self {
+        AsteroidTiers::from_toml(include_str!("../asteroids.toml")).expect("built in asteroids.toml is invalid")
     }
 }
 
-pub struct AsteroidCollider;
+// Everything an asteroid of the given type needs besides its position, heading off
+// at a random speed and spin from the type's ranges
+pub fn asteroid_parts(tier: &AsteroidTier, index: usize, heading: f64, rng: &mut StdRng, collisions: AsteroidCollisions)
+    -> (components::Velocity, components::AngularVelocity, components::Renderable, components::Asteroid, components::Collider) {
+    let speed = rng.gen_range(tier.speed.0..=tier.speed.1);
+    let spin = rng.gen_range(tier.spin.0..=tier.spin.1);
+    (
+        components::Velocity::from_heading(heading, speed),
+        components::AngularVelocity(spin),
+        components::Renderable{
+            tex_name: tier.sprite.clone(),
+            i_w: 100,
+            i_h: 100,
+            o_w: tier.size,
+            o_h: tier.size,
+            frame: 0,
+            total_frames: 1
+        },
+        components::Asteroid{
+            tier: index,
+            hit_points: tier.hit_points
+        },
+        asteroid_collider(tier.radius, collisions)
+    )
+}
 
-impl<'a> System<'a> for AsteroidCollider{
+// Headings for the pieces of a broken asteroid, fanned out across the half circle around its heading
+pub fn split_headings(heading: f64, pieces: usize) -> Vec<f64> {
+    if pieces == 1 {
+        return vec![heading];
+    }
+    (0..pieces).map(|i| heading - 90.0 + 180.0 * i as f64 / (pieces - 1) as f64).collect()
+}
+
+// Turns asteroids heading off screen back round when the screen doesn't wrap
+impl<'a> System<'a> for WallBouncer{
+    type SystemData = (
+        ReadStorage<'a, components::Position>,
+        WriteStorage<'a, components::Velocity>,
+        ReadStorage<'a, components::Renderable>,
+        ReadStorage<'a, components::Asteroid>,
+        Read<'a,GameMode>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (positions, mut velocities, rends, asteroids, mode) = data;
+        if mode.wraps() {
+            return;
+        }
+        for(pos,vel,rend,_) in (&positions, &mut velocities, &rends, &asteroids).join(){
+            let half_width = (rend.o_w / 2) as f64;
+            let half_height = (rend.o_h / 2) as f64;
+
+            if (pos.x > crate::SCREEN_WIDTH as f64 - half_width && vel.x > 0.0)
+                || (pos.x < half_width && vel.x < 0.0){
+                    vel.x = -vel.x;
+            }
+            if (pos.y > crate::SCREEN_HEIGHT as f64 - half_height && vel.y > 0.0)
+                || (pos.y < half_height && vel.y < 0.0){
+                    vel.y = -vel.y;
+            }
+        }
+    }
+}
+
+// Asteroids only collide with each other when asteroid collisions are turned on
+pub fn asteroid_collider(radius: f64, collisions: AsteroidCollisions) -> components::Collider {
+    let mut mask = collision::LAYER_PLAYER | collision::LAYER_MISSILE;
+    if collisions.0 {
+        mask |= collision::LAYER_ASTEROID;
+    }
+    components::Collider::circle(radius, collision::LAYER_ASTEROID, mask)
+}
+
+// Mass grows with the area of the asteroid
+fn asteroid_mass(radius: f64) -> f64 {
+    radius * radius
+}
+
+// Bounces colliding asteroids off each other as perfectly elastic discs and
+// shares out their spin
+#[derive(Default)]
+pub struct AsteroidBouncer {
+    reader: Option<ReaderId<CollisionEvent>>
+}
+
+impl<'a> System<'a> for AsteroidBouncer{
     type SystemData = (
         WriteStorage<'a, components::Position>,
-        WriteStorage<'a, components::Renderable>,
-        WriteStorage<'a, components::Player>,
-        WriteStorage<'a, components::Asteroid>,
-        Entities<'a>
+        WriteStorage<'a, components::Velocity>,
+        WriteStorage<'a, components::AngularVelocity>,
+        ReadStorage<'a, components::Collider>,
+        ReadStorage<'a, components::Asteroid>,
+        Read<'a,CollisionEvents>,
+        Read<'a,GameMode>
     );
 
-    // fn run(&mut self, mut data: Self::SystemData) {
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(collision::register_reader(world));
+    }
+
     fn run(&mut self, data: Self::SystemData) {
-        let (positions, rends, mut players, asteroids, entities) = data;
-        for(players_pos, player_rend, player, entity) in (&positions,&rends, &mut players, &entities).join(){
-            if player.invulnerable || player.died {
+        let (mut positions, mut velocities, mut spins, colliders, asteroids, events, mode) = data;
+        let reader = self.reader.as_mut().expect("AsteroidBouncer used without setup");
+        for event in events.read(reader){
+            let (first, second) = match event.matching(asteroids.mask(), asteroids.mask()) {
+                Some(pair) => pair,
+                None => continue
+            };
+            let (radius_a, radius_b) = match (colliders.get(first), colliders.get(second)) {
+                (Some(a), Some(b)) => (a.bounding_radius(), b.bounding_radius()),
+                _ => continue
+            };
+            let (diff_x, diff_y) = match (positions.get(first), positions.get(second)) {
+                //Collision normal from a to b, across the screen edge if that is closer
+                (Some(a), Some(b)) => game::position_delta(*mode, b, a),
+                _ => continue
+            };
+            let (vel_a, vel_b) = match (velocities.get(first), velocities.get(second)) {
+                (Some(a), Some(b)) => ((a.x, a.y), (b.x, b.y)),
+                _ => continue
+            };
+            let (spin_a, spin_b) = match (spins.get(first), spins.get(second)) {
+                (Some(a), Some(b)) => (a.0, b.0),
+                _ => continue
+            };
+
+            let dist = (diff_x * diff_x + diff_y * diff_y).sqrt();
+            if dist == 0.0 {
                 continue;
             }
-            for(asteroid_pos, asteroid_rend, _) in (&positions, &rends, &asteroids).join(){
-                if asteroid_pos.section != players_pos.section {
-                    continue;
-                }
-                let diff_x: f64 = (players_pos.x - asteroid_pos.x).abs();
-                let diff_y: f64 = (players_pos.y - asteroid_pos.y).abs();
-                let hype: f64 = ((diff_x*diff_x) + (diff_y*diff_y));
-
-                if hype < ((player_rend.o_w + asteroid_rend.o_w) as f64 / 2.0)*((player_rend.o_w + asteroid_rend.o_w) as f64 / 2.0) {
-                    println!("Collision Detected!");
-                    if player.lives > 1 {
-                        player.died = true;
-                    } else {
-                        let _ = entities.delete(entity);
-                    }
-                }
+            let normal = (diff_x / dist, diff_y / dist);
+
+            let mass_a = asteroid_mass(radius_a);
+            let mass_b = asteroid_mass(radius_b);
+
+            //Push them apart so they don't stay stuck inside each other, the lighter one moving further
+            let overlap = radius_a + radius_b - dist;
+            let push_a = overlap.max(0.0) * mass_b / (mass_a + mass_b);
+            let push_b = overlap.max(0.0) * mass_a / (mass_a + mass_b);
+
+            let (new_a, new_b) = collision::elastic_bounce(vel_a, mass_a, vel_b, mass_b, normal);
+
+            //Spin settles towards the shared value, keeping the total angular momentum (inertia ~ mass * radius^2)
+            let inertia_a = mass_a * radius_a * radius_a;
+            let inertia_b = mass_b * radius_b * radius_b;
+            let shared_spin = (inertia_a * spin_a + inertia_b * spin_b) / (inertia_a + inertia_b);
+
+            if let Some(pos) = positions.get_mut(first) {
+                pos.x -= normal.0 * push_a;
+                pos.y -= normal.1 * push_a;
+            }
+            if let Some(pos) = positions.get_mut(second) {
+                pos.x += normal.0 * push_b;
+                pos.y += normal.1 * push_b;
+            }
+            velocities.insert(first, components::Velocity{x: new_a.0, y: new_a.1}).ok();
+            velocities.insert(second, components::Velocity{x: new_b.0, y: new_b.1}).ok();
+            spins.insert(first, components::AngularVelocity((spin_a + shared_spin) / 2.0)).ok();
+            spins.insert(second, components::AngularVelocity((spin_b + shared_spin) / 2.0)).ok();
+        }
+    }
+}
+
+// Ships that run into an asteroid lose a life
+#[derive(Default)]
+pub struct AsteroidCollider {
+    reader: Option<ReaderId<CollisionEvent>>
+}
+
+impl<'a> System<'a> for AsteroidCollider{
+    type SystemData = (
+        WriteStorage<'a, components::Player>,
+        ReadStorage<'a, components::Asteroid>,
+        Entities<'a>,
+        Read<'a,CollisionEvents>
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(collision::register_reader(world));
+    }
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut players, asteroids, entities, events) = data;
+        let reader = self.reader.as_mut().expect("AsteroidCollider used without setup");
+        for event in events.read(reader){
+            let (entity, _) = match event.matching(players.mask(), asteroids.mask()) {
+                Some(pair) => pair,
+                None => continue
+            };
+            if let Some(player) = players.get_mut(entity) {
+                game::kill_player(player, entity, &entities);
             }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROCK: &str = "[rock]\nsprite = \"img/asteroid1.png\"\nsize = 50\nradius = 25.0\nspeed = [100.0, 150.0]\nspin = [-100.0, 100.0]\npoints = 50\n";
+
+    #[test]
+    fn built_in_tiers_load() {
+        let tiers = AsteroidTiers::from_toml(include_str!("../asteroids.toml")).unwrap();
+        assert!(tiers.find("large").is_some());
+    }
+
+    #[test]
+    fn misspelt_hit_points_is_refused() {
+        let error = AsteroidTiers::from_toml(&format!("{}hitpoints = 3\nspawn_weight = 1\n", ROCK)).unwrap_err();
+        assert!(error.contains("unknown field `hitpoints`"), "{}", error);
+    }
+
+    #[test]
+    fn misspelt_spawn_weight_is_refused() {
+        let error = AsteroidTiers::from_toml(&format!("{}spawn_weigth = 1\n", ROCK)).unwrap_err();
+        assert!(error.contains("unknown field `spawn_weigth`"), "{}", error);
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let contents = format!("{}hit_points = 0\nsplits_into = [\"pebble\"]\n", ROCK);
+        let error = AsteroidTiers::from_toml(&contents).unwrap_err();
+        assert_eq!(error, "[rock] hit_points must be at least 1\n[rock] splits into unknown type \"pebble\"\nno asteroid type has a spawn_weight, waves would be empty");
+    }
+
+    #[test]
+    fn splitting_into_itself_is_refused() {
+        let error = AsteroidTiers::from_toml(&format!("{}spawn_weight = 1\nsplits_into = [\"rock\"]\n", ROCK)).unwrap_err();
+        assert_eq!(error, "[rock] splits back into itself, its pieces would never run out");
+    }
+}
@@ -0,0 +1,313 @@
+use specs::{System, ReadStorage, Read, Write, Join, Entity, World, BitSet};
+use specs::prelude::Entities;
+use specs::shrev::EventChannel;
+
+use crate::{components, game, spatial, GameMode};
+use crate::components::{Collider, ColliderShape};
+
+// Collision layers. An entity collides with anything whose layer is in its mask (or the other way round).
+pub const LAYER_PLAYER: u32 = 1 << 0;
+pub const LAYER_ASTEROID: u32 = 1 << 1;
+pub const LAYER_MISSILE: u32 = 1 << 2;
+pub const LAYER_SAUCER: u32 = 1 << 3;
+pub const LAYER_SAUCER_MISSILE: u32 = 1 << 4;
+
+// Two entities whose colliders overlapped this tick, in no particular order
+#[derive(Clone, Copy, Debug)]
+pub struct CollisionEvent {
+    pub a: Entity,
+    pub b: Entity
+}
+
+impl CollisionEvent {
+    // The pair ordered so the first entity is in `firsts` and the second in `seconds`,
+    // e.g. `event.matching(players.mask(), asteroids.mask())`
+    pub fn matching(&self, firsts: &BitSet, seconds: &BitSet) -> Option<(Entity, Entity)> {
+        if firsts.contains(self.a.id()) && seconds.contains(self.b.id()) {
+            Some((self.a, self.b))
+        } else if firsts.contains(self.b.id()) && seconds.contains(self.a.id()) {
+            Some((self.b, self.a))
+        } else {
+            None
+        }
+    }
+}
+
+pub type CollisionEvents = EventChannel<CollisionEvent>;
+
+// Registers a reader for a system that reacts to collisions, called from its setup
+pub fn register_reader(world: &mut World) -> specs::ReaderId<CollisionEvent> {
+    world.entry::<CollisionEvents>().or_insert_with(CollisionEvents::new).register_reader()
+}
+
+impl Collider {
+    pub fn circle(radius: f64, layer: u32, mask: u32) -> Self {
+        Collider {
+            shape: ColliderShape::Circle(radius),
+            layer,
+            mask
+        }
+    }
+
+    pub fn polygon(points: Vec<(f64, f64)>, layer: u32, mask: u32) -> Self {
+        Collider {
+            shape: ColliderShape::Polygon(points),
+            layer,
+            mask
+        }
+    }
+
+    // Radius of a circle around the position that holds the whole shape, for the broadphase
+    pub fn bounding_radius(&self) -> f64 {
+        match &self.shape {
+            ColliderShape::Circle(radius) => *radius,
+            ColliderShape::Polygon(points) => points.iter()
+                .map(|(x, y)| (x * x + y * y).sqrt())
+                .fold(0.0, f64::max)
+        }
+    }
+
+    pub fn interacts_with(&self, other: &Collider) -> bool {
+        self.mask & other.layer != 0 || other.mask & self.layer != 0
+    }
+}
+
+// Finds every overlapping pair of colliders and sends a CollisionEvent for each.
+// The spatial grid narrows the candidates down before the exact shape test.
+pub struct CollisionDetector;
+
+impl<'a> System<'a> for CollisionDetector {
+    type SystemData = (
+        ReadStorage<'a, components::Position>,
+        ReadStorage<'a, Collider>,
+        Entities<'a>,
+        Read<'a, spatial::SpatialGrid>,
+        Read<'a, GameMode>,
+        Write<'a, CollisionEvents>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (positions, colliders, entities, grid, mode, mut events) = data;
+        let mut found = Vec::new();
+
+        for (pos, collider, entity) in (&positions, &colliders, &entities).join() {
+            for other in grid.query(pos.x, pos.y, collider.bounding_radius()) {
+                //Each pair is seen from both sides, only report it from the lower id
+                if other.id() <= entity.id() {
+                    continue;
+                }
+                let other_collider = match colliders.get(other) {
+                    Some(other_collider) if collider.interacts_with(other_collider) => other_collider,
+                    _ => continue
+                };
+                let other_pos = match positions.get(other) {
+                    Some(other_pos) => other_pos,
+                    None => continue
+                };
+                if overlaps(collider, pos.rot, other_collider, other_pos.rot, game::position_delta(*mode, other_pos, pos)) {
+                    found.push(CollisionEvent{a: entity, b: other});
+                }
+            }
+        }
+
+        events.iter_write(found);
+    }
+}
+
+// Exact test between two shapes, the second one offset from the first by `offset`
+pub fn overlaps(a: &Collider, a_rot: f64, b: &Collider, b_rot: f64, offset: (f64, f64)) -> bool {
+    match (&a.shape, &b.shape) {
+        (ColliderShape::Circle(ra), ColliderShape::Circle(rb)) => {
+            offset.0 * offset.0 + offset.1 * offset.1 < (ra + rb) * (ra + rb)
+        },
+        (ColliderShape::Polygon(points), ColliderShape::Circle(radius)) => {
+            polygon_circle(&place(points, a_rot, (0.0, 0.0)), offset, *radius)
+        },
+        (ColliderShape::Circle(radius), ColliderShape::Polygon(points)) => {
+            polygon_circle(&place(points, b_rot, offset), (0.0, 0.0), *radius)
+        },
+        (ColliderShape::Polygon(a_points), ColliderShape::Polygon(b_points)) => {
+            polygon_polygon(&place(a_points, a_rot, (0.0, 0.0)), &place(b_points, b_rot, offset))
+        }
+    }
+}
+
+// Rotates the shape's points by the entity's rotation (degrees, clockwise) and moves them to `at`
+fn place(points: &[(f64, f64)], rot: f64, at: (f64, f64)) -> Vec<(f64, f64)> {
+    let (sin, cos) = rot.to_radians().sin_cos();
+    points.iter()
+        .map(|(x, y)| (at.0 + x * cos - y * sin, at.1 + x * sin + y * cos))
+        .collect()
+}
+
+// Velocities of two discs after a perfectly elastic hit along `normal` (unit length, from a to b).
+// Only exchanges momentum while they are still moving towards each other.
+pub fn elastic_bounce(vel_a: (f64, f64), mass_a: f64, vel_b: (f64, f64), mass_b: f64, normal: (f64, f64)) -> ((f64, f64), (f64, f64)) {
+    let closing = (vel_a.0 - vel_b.0) * normal.0 + (vel_a.1 - vel_b.1) * normal.1;
+    if closing <= 0.0 {
+        return (vel_a, vel_b);
+    }
+    let impulse = 2.0 * closing / (mass_a + mass_b);
+    (
+        (vel_a.0 - impulse * mass_b * normal.0, vel_a.1 - impulse * mass_b * normal.1),
+        (vel_b.0 + impulse * mass_a * normal.0, vel_b.1 + impulse * mass_a * normal.1)
+    )
+}
+
+fn polygon_circle(polygon: &[(f64, f64)], center: (f64, f64), radius: f64) -> bool {
+    if contains_point(polygon, center) {
+        return true;
+    }
+    for i in 0..polygon.len() {
+        let start = polygon[i];
+        let end = polygon[(i + 1) % polygon.len()];
+        if distance_to_segment_squared(center, start, end) < radius * radius {
+            return true;
+        }
+    }
+    false
+}
+
+// Separating axis test: convex polygons overlap unless some edge normal separates them
+fn polygon_polygon(a: &[(f64, f64)], b: &[(f64, f64)]) -> bool {
+    for polygon in [a, b] {
+        for i in 0..polygon.len() {
+            let start = polygon[i];
+            let end = polygon[(i + 1) % polygon.len()];
+            let axis = (start.1 - end.1, end.0 - start.0);
+            let (a_min, a_max) = project(a, axis);
+            let (b_min, b_max) = project(b, axis);
+            if a_max < b_min || b_max < a_min {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn project(polygon: &[(f64, f64)], axis: (f64, f64)) -> (f64, f64) {
+    let mut min = f64::MAX;
+    let mut max = f64::MIN;
+    for (x, y) in polygon {
+        let dot = x * axis.0 + y * axis.1;
+        min = min.min(dot);
+        max = max.max(dot);
+    }
+    (min, max)
+}
+
+// Inside when the point is on the same side of every edge
+fn contains_point(polygon: &[(f64, f64)], point: (f64, f64)) -> bool {
+    let mut sign = 0.0;
+    for i in 0..polygon.len() {
+        let start = polygon[i];
+        let end = polygon[(i + 1) % polygon.len()];
+        let cross = (end.0 - start.0) * (point.1 - start.1) - (end.1 - start.1) * (point.0 - start.0);
+        if cross != 0.0 {
+            if sign != 0.0 && cross.signum() != sign {
+                return false;
+            }
+            sign = cross.signum();
+        }
+    }
+    true
+}
+
+fn distance_to_segment_squared(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let segment = (end.0 - start.0, end.1 - start.1);
+    let length_squared = segment.0 * segment.0 + segment.1 * segment.1;
+    let t = if length_squared > 0.0 {
+        (((point.0 - start.0) * segment.0 + (point.1 - start.1) * segment.1) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let closest = (start.0 + segment.0 * t, start.1 + segment.1 * t);
+    let diff = (point.0 - closest.0, point.1 - closest.1);
+    diff.0 * diff.0 + diff.1 * diff.1
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::{Builder, RunNow, WorldExt};
+
+    use super::*;
+    use crate::components::Position;
+
+    // A thin bar 80 long, pointing up and down when not rotated
+    fn bar() -> Collider {
+        Collider::polygon(vec![(-5.0, -40.0), (5.0, -40.0), (5.0, 40.0), (-5.0, 40.0)], LAYER_PLAYER, LAYER_ASTEROID)
+    }
+
+    fn ball() -> Collider {
+        Collider::circle(5.0, LAYER_ASTEROID, 0)
+    }
+
+    #[test]
+    fn rotated_polygon_against_circle() {
+        //Beside the bar until it is turned on its side
+        assert!(!overlaps(&bar(), 0.0, &ball(), 0.0, (30.0, 0.0)));
+        assert!(overlaps(&bar(), 90.0, &ball(), 0.0, (30.0, 0.0)));
+
+        //Past the end of the bar once it is turned
+        assert!(overlaps(&bar(), 0.0, &ball(), 0.0, (0.0, 30.0)));
+        assert!(!overlaps(&bar(), 90.0, &ball(), 0.0, (0.0, 30.0)));
+
+        //The same with the circle first and the bar offset from it
+        assert!(!overlaps(&ball(), 0.0, &bar(), 0.0, (30.0, 0.0)));
+        assert!(overlaps(&ball(), 0.0, &bar(), 90.0, (30.0, 0.0)));
+    }
+
+    // Runs the broadphase and the detector over two overlapping colliders
+    fn events_between(a: Collider, b: Collider) -> usize {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<Collider>();
+        world.insert(spatial::SpatialGrid::new());
+        world.insert(GameMode::Bounce);
+        let mut reader = register_reader(&mut world);
+        world.create_entity().with(Position{x: 500.0, y: 500.0, rot: 0.0}).with(a).build();
+        world.create_entity().with(Position{x: 505.0, y: 500.0, rot: 0.0}).with(b).build();
+
+        spatial::SpatialGridBuilder.run_now(&world);
+        CollisionDetector.run_now(&world);
+        let events = world.read_resource::<CollisionEvents>();
+        events.read(&mut reader).count()
+    }
+
+    #[test]
+    fn layers_outside_each_others_masks_do_not_collide() {
+        let missile = || Collider::circle(5.0, LAYER_MISSILE, LAYER_ASTEROID | LAYER_SAUCER);
+        assert_eq!(events_between(missile(), missile()), 0);
+
+        //Only one side has to list the other
+        let asteroid = Collider::circle(5.0, LAYER_ASTEROID, 0);
+        assert_eq!(events_between(missile(), asteroid), 1);
+    }
+
+    #[test]
+    fn equal_masses_head_on_swap_velocities() {
+        let (a, b) = elastic_bounce((100.0, 0.0), 4.0, (-30.0, 0.0), 4.0, (1.0, 0.0));
+        assert_eq!(a, (-30.0, 0.0));
+        assert_eq!(b, (100.0, 0.0));
+    }
+
+    #[test]
+    fn unequal_masses_keep_momentum_and_energy() {
+        let (mass_a, mass_b) = (2500.0, 625.0);
+        let (vel_a, vel_b) = ((120.0, -40.0), (-60.0, 90.0));
+        let normal = (0.6, 0.8);
+        let (new_a, new_b) = elastic_bounce(vel_a, mass_a, vel_b, mass_b, normal);
+
+        let momentum = |a: (f64, f64), b: (f64, f64)| (mass_a * a.0 + mass_b * b.0, mass_a * a.1 + mass_b * b.1);
+        let energy = |a: (f64, f64), b: (f64, f64)| mass_a * (a.0 * a.0 + a.1 * a.1) + mass_b * (b.0 * b.0 + b.1 * b.1);
+        let (before, after) = (momentum(vel_a, vel_b), momentum(new_a, new_b));
+        assert!((before.0 - after.0).abs() < 1e-6 && (before.1 - after.1).abs() < 1e-6);
+        assert!((energy(vel_a, vel_b) - energy(new_a, new_b)).abs() < 1e-3);
+        assert_ne!(new_a, vel_a); //They were closing, so something has to change
+    }
+
+    #[test]
+    fn separating_discs_are_left_alone() {
+        assert_eq!(elastic_bounce((-10.0, 0.0), 1.0, (10.0, 0.0), 1.0, (1.0, 0.0)), ((-10.0, 0.0), (10.0, 0.0)));
+    }
+}
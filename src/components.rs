@@ -6,8 +6,25 @@ use vector2d::Vector2D;
 pub struct Position {
     pub x: f64,
     pub y: f64,
-    pub rot: f64,
-    pub section: u32
+    pub rot: f64 //Way the entity faces in degrees, clockwise from up. Also how the sprite is drawn
+}
+
+// Pixels per second, moved by the movement system
+#[derive(Component)]
+pub struct Velocity {
+    pub x: f64,
+    pub y: f64
+}
+
+// Degrees per second added to Position.rot
+#[derive(Component)]
+pub struct AngularVelocity(pub f64);
+
+// Position at the start of the last simulation tick, used to interpolate rendering
+#[derive(Component)]
+pub struct PreviousPosition {
+    pub x: f64,
+    pub y: f64
 }
 
 // Renderable Item and image deets
@@ -19,62 +36,87 @@ pub struct Renderable {
     pub o_w: u32, //Output Width
     pub o_h: u32, //Output Height
     pub frame: u32, //Current Frame
-    pub total_frames: u32, //Total Frames
-    pub rot: f64 //Rotation of Image
+    pub total_frames: u32 //Total Frames
+}
+
+pub enum ColliderShape {
+    Circle(f64), //Radius
+    Polygon(Vec<(f64, f64)>) //Convex, points relative to the position with rotation 0
+}
+
+// Collision shape plus the layer it is on and the layers it collides with
+#[derive(Component)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    pub layer: u32,
+    pub mask: u32
 }
 
 // Player Component
 #[derive(Component)]
 pub struct Player {
     pub impulse: Vector2D<f64>,
-    pub cur_speed: Vector2D<f64>,
     pub lives: u32,
     pub died: bool,
-    pub invulnerable: bool
+    pub invulnerable: bool, //Debug cheat, separate from the respawn shield
+    pub hyperspace: f64, //Seconds left before re-entering from hyperspace, 0 when in normal space
+    pub hyperspace_cooldown: f64, //Seconds until the hyperspace drive can be used again
+    pub respawning: bool, //Off screen after losing a life, waiting for a safe spot
+    pub respawn_wait: f64, //Seconds spent waiting to respawn
+    pub shield: f64, //Seconds of invulnerability left after respawning
+    pub life_flash: f64 //Seconds left flashing the lives counter after an extra life
 }
 
 // Asteroid Component
 #[derive(Component)]
 pub struct Asteroid {
-    pub speed: f64,
-    pub rot_speed: f64
+    pub tier: usize, //Index into the AsteroidTiers table
+    pub hit_points: u32 //Missile hits left before it breaks
 }
 
 #[derive(Component)]
 pub struct Missile {
-    pub speed: f64
+    pub lifetime: f64, //Seconds left before it burns out when the screen wraps
+    pub hostile: bool //Fired by a saucer at the player
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SaucerSize {
+    Large, //Slow and fires at random
+    Small //Fast and aims at the player
+}
+
+#[derive(Component)]
+pub struct Saucer {
+    pub size: SaucerSize,
+    pub fire_timer: f64, //Seconds until it fires again
+    pub turn_timer: f64, //Seconds until it changes its vertical direction
+    pub travelled: f64 //Horizontal distance covered, it leaves once it has crossed the screen
+}
+
+// A short animation that plays through its frames once and then disappears
+#[derive(Component)]
+pub struct Effect {
+    pub lifetime: f64, //Seconds left
+    pub duration: f64 //Seconds the whole animation lasts
 }
 
 pub struct PendingAsteroid{
     pub x: f64,
     pub y: f64,
-    pub rot: f64,
-    pub section: u32,
-    pub size: u32
+    pub heading: f64,
+    pub tier: usize
 }
 
 #[derive(Component)]
 pub struct GameData{
     pub score: u32,
     pub level: u32,
-    pub showControls: bool
+    pub show_controls: bool,
+    pub extra_lives: u32 //Earned from the score but not handed to the ship yet
 }
 
 // #[derive(Component)]
 // pub struct Star{
 //     pub size: u32
 // }
-
-#[derive(PartialEq)]
-pub enum SoundCueType{
-    PlaySound,
-    LoopSound,
-    StopSound
-}
-
-#[derive(Component)]
-pub struct SoundCue{
-    pub filename: String,
-    pub sc_type: SoundCueType
-}
-
@@ -0,0 +1,53 @@
+use specs::prelude::*;
+use specs::{Entities, Join};
+
+use crate::{assets, components};
+
+const RING_TIME: f64 = 0.4;
+const RING_FRAMES: u32 = 4;
+
+// An expanding ring, for hyperspace jumps and explosions
+pub fn ring(size: u32) -> components::Renderable {
+    components::Renderable{
+        tex_name: String::from(assets::RING_TEXTURE),
+        i_w: 100,
+        i_h: 100,
+        o_w: size,
+        o_h: size,
+        frame: 0,
+        total_frames: RING_FRAMES
+    }
+}
+
+pub fn ring_effect() -> components::Effect {
+    components::Effect{
+        lifetime: RING_TIME,
+        duration: RING_TIME
+    }
+}
+
+// Steps effects through their frames and removes them once they have played
+pub struct EffectAnimator;
+
+impl<'a> System<'a> for EffectAnimator {
+    type SystemData = (
+        WriteStorage<'a, components::Effect>,
+        WriteStorage<'a, components::Renderable>,
+        Entities<'a>,
+        Read<'a, crate::DeltaTime>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut effects, mut renderables, entities, deltatime) = data;
+
+        for (effect, rend, entity) in (&mut effects, &mut renderables, &entities).join() {
+            effect.lifetime -= deltatime.0;
+            if effect.lifetime <= 0.0 {
+                entities.delete(entity).ok();
+                continue;
+            }
+            let progress = 1.0 - effect.lifetime / effect.duration;
+            rend.frame = ((progress * rend.total_frames as f64) as u32).min(rend.total_frames - 1);
+        }
+    }
+}
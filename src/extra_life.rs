@@ -0,0 +1,49 @@
+use specs::prelude::*;
+use specs::Join;
+
+use crate::components;
+use crate::sound::{Sound, SoundEvents};
+
+pub const MAX_LIVES: u32 = 5;
+const LIFE_FLASH_TIME: f64 = 2.0;
+const FLASHES_PER_SECOND: f64 = 6.0;
+
+// The lives counter flashes for a moment after an extra life
+pub fn flashed_on(player: &components::Player) -> bool {
+    player.life_flash > 0.0 && ((player.life_flash * FLASHES_PER_SECOND) as u32).is_multiple_of(2)
+}
+
+// Hands the extra lives earned by scoring to the ship, up to MAX_LIVES
+pub struct ExtraLifeAwarder;
+
+impl<'a> System<'a> for ExtraLifeAwarder {
+    type SystemData = (
+        WriteStorage<'a, components::GameData>,
+        WriteStorage<'a, components::Player>,
+        Write<'a, SoundEvents>,
+        Read<'a, crate::DeltaTime>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut game_data, mut players, mut sounds, deltatime) = data;
+
+        let mut earned: u32 = 0;
+        for gamedata in (&mut game_data).join() {
+            earned += std::mem::take(&mut gamedata.extra_lives);
+        }
+
+        let mut awarded = false;
+        for player in (&mut players).join() {
+            player.life_flash = (player.life_flash - deltatime.0).max(0.0);
+            if earned > 0 && player.lives < MAX_LIVES {
+                player.lives = (player.lives + earned).min(MAX_LIVES);
+                player.life_flash = LIFE_FLASH_TIME;
+                awarded = true;
+            }
+        }
+
+        if awarded {
+            sounds.play(Sound::ExtraLife);
+        }
+    }
+}
@@ -1,104 +1,95 @@
-use std::collections::HashMap;
-use specs::{World, WorldExt, Builder, Join};
+use specs::{World, WorldExt, Builder, Join, Dispatcher, DispatcherBuilder};
 
 use rand::Rng;
 
-use crate::{components, SCREEN_HEIGHT, SCREEN_WIDTH, SHOOT_FILENAME};
+use crate::{assets, components, asteroid, missile, movement, saucer, hyperspace, respawn, extra_life, effect, wave, spatial, collision, SCREEN_HEIGHT, SCREEN_WIDTH};
 use crate::utils;
+use crate::sound::{Sound, SoundEvents};
+use crate::input::{ActionState, InputAction};
+use crate::{GameMode, GamePhase};
 
+const LEVEL_TRANSITION_TIME: f64 = 2.0;
 const ROTATION_SPEED: f64 = 120.0;
 const IMPULSE_SPEED: f64 = 300.0;
-pub fn update(ecs: &mut World, key_manager: &mut HashMap<String,bool>, deltaTime: f64){
+pub fn update(ecs: &mut World, key_manager: &mut ActionState, delta_time: f64){
     //Check status of the game world
-    let mut must_reload_world = false;
-    let mut current_player_position = components::Position{x:0.0, y: 0.0, rot: 0.0, section: 0};
+    let mut game_over = false;
 
     {
-        let mut players = ecs.write_storage::<crate::components::Player>();
-        let mut positions = ecs.write_storage::<crate::components::Position>();
-        for(pos,player) in (&mut positions,&mut players).join(){
-            if player.died {
-                player.died = false;
-                player.lives -= 1;
-                player.cur_speed.x = 0.0;
-                player.cur_speed.y = 0.0;
-                player.impulse.x = 0.0;
-                player.impulse.y = 0.0;
-                pos.x = crate::SCREEN_WIDTH as f64 / 2.0;
-                pos.y = crate::SCREEN_HEIGHT as f64 / 2.0;
-            }
-            current_player_position.x = pos.x;
-            current_player_position.y = pos.y;
-        }
+        let players = ecs.read_storage::<crate::components::Player>();
         if players.join().count() < 1 {
-            must_reload_world = true;
+            game_over = true;
         }
 
     }
 
-    if must_reload_world {
-        ecs.delete_all();
-        load_world(ecs);
+    if game_over {
+        ecs.write_resource::<crate::GameStatus>().phase = GamePhase::GameOver;
+        stop_loops(ecs);
+        return;
     }
 
-    let mut must_create_asteroid = false;
-    let mut number_asteroids: u32 = 0;
+    let mut next_wave = None;
     {
         let asteroids = ecs.read_storage::<crate::components::Asteroid>();
-        if asteroids.join().count() < 1 {
-            must_create_asteroid = true;
-
-            let mut gamedatas = ecs.write_storage::<crate::components::GameData>();
-            for mut gamedata in (&mut gamedatas).join(){
+        let mut status = ecs.write_resource::<crate::GameStatus>();
+        let mut current_wave = ecs.write_resource::<wave::CurrentWave>();
+        let mut gamedatas = ecs.write_storage::<crate::components::GameData>();
+        if status.phase == GamePhase::Playing && asteroids.join().count() < 1 {
+            //Field cleared, give the player a moment before the next wave
+            status.phase = GamePhase::LevelTransition;
+            status.transition_time = LEVEL_TRANSITION_TIME;
+            for gamedata in (&mut gamedatas).join(){
                 gamedata.level += 1;
-                number_asteroids = (gamedata.level /3) + 1;
+            }
+        } else if status.phase == GamePhase::Playing && wave::timed_out(&mut current_wave, delta_time) {
+            //Out of time, the next wave arrives on top of what is left
+            for gamedata in (&mut gamedatas).join(){
+                gamedata.level += 1;
+                next_wave = Some(gamedata.level);
+            }
+        } else if status.phase == GamePhase::LevelTransition {
+            status.transition_time -= delta_time;
+            if status.transition_time <= 0.0 {
+                status.phase = GamePhase::Playing;
+                for gamedata in (&mut gamedatas).join(){
+                    next_wave = Some(gamedata.level);
+                }
             }
         }
     }
 
-    if must_create_asteroid {
-        let mut asteroid_count: u32 = 0;
-        while asteroid_count < number_asteroids {
-            let mut rng = rand::thread_rng();
-            let next_x = rng.gen_range(50.0..crate::SCREEN_WIDTH as f64 - 50.0);
-            let next_y = rng.gen_range(50.0..crate::SCREEN_HEIGHT as f64 - 50.0);
-            let next_rot = rng.gen_range(0.0..360.0);
-
-            let diff_x = (next_x - current_player_position.x).abs();
-            let diff_y = (next_y - current_player_position.y).abs();
-            let dist = (diff_x * diff_x + diff_y * diff_y);
-            if dist < 22500.0 {
-                continue;
-            }
-
-            asteroid_count += 1;
-            let new_asteroid = components::Position{
-                x: next_x,
-                y: next_y,
-                rot: next_rot,
-                section: 0
-            };
-            create_asteroid(ecs,new_asteroid,100);
-        }
+    if let Some(level) = next_wave {
+        wave::spawn_wave(ecs, level);
+    }
 
+    if ecs.read_resource::<crate::GameStatus>().phase == GamePhase::Playing {
+        saucer::update_spawner(ecs, delta_time);
     }
 
-    let mut player_pos = components::Position{x: 0.0, y: 0.0, rot: 0.0,section: 0};
+    let mut player_pos = components::Position{x: 0.0, y: 0.0, rot: 0.0};
+    let mut player_vel = components::Velocity{x: 0.0, y: 0.0};
     let mut must_fire_missile = false;
     let mut thruster_pushed = false;
+    let mut jumping_ship = None;
     {
         let mut positions =  ecs.write_storage::<crate::components::Position>();
         let mut players = ecs.write_storage::<crate::components::Player>();
-        let mut renderables = ecs.write_storage::<crate::components::Renderable>();
+        let mut velocities = ecs.write_storage::<crate::components::Velocity>();
+        let entities = ecs.entities();
 
-        for(player,pos, renderable) in (&mut players, &mut positions, &mut renderables).join(){
+        for(player,pos,vel,entity) in (&mut players, &mut positions, &mut velocities, &entities).join(){
+            //The ship can't be controlled while it is in hyperspace or waiting to respawn
+            if !in_play(player) {
+                continue;
+            }
 
-            if crate::utils::is_key_pressed(&key_manager, "D"){
-                pos.rot += ROTATION_SPEED * deltaTime;
+            if crate::utils::is_key_pressed(key_manager, &InputAction::RotateRight){
+                pos.rot += ROTATION_SPEED * key_manager.rotate_amount() * delta_time;
                 thruster_pushed = true;
             }
-            if crate::utils::is_key_pressed(&key_manager, "A"){
-                pos.rot -= ROTATION_SPEED*deltaTime;
+            if crate::utils::is_key_pressed(key_manager, &InputAction::RotateLeft){
+                pos.rot -= ROTATION_SPEED * key_manager.rotate_amount() * delta_time;
                 thruster_pushed = true;
             }
             if pos.rot > 360.0 {
@@ -108,115 +99,283 @@ pub fn update(ecs: &mut World, key_manager: &mut HashMap<String,bool>, deltaTime
                 pos.rot += 360.0;
             }
 
-            if crate::utils::is_key_pressed(&key_manager, "W"){
+            if crate::utils::is_key_pressed(key_manager, &InputAction::Thrust){
                 player.impulse.y -= pos.rot.to_radians().cos() * IMPULSE_SPEED;
                 player.impulse.x += pos.rot.to_radians().sin() * IMPULSE_SPEED;
                 thruster_pushed = true;
             }
-            update_movement(pos,player,deltaTime as f64);
+            update_movement(vel,player,delta_time);
 
-            if pos.x > crate::SCREEN_WIDTH.into() {
-                pos.x -= crate::SCREEN_WIDTH as f64;
-            }
-            else if pos.x < 0.0 {
-                pos.x += crate::SCREEN_WIDTH as f64;
-            }
-
-            if pos.y > crate::SCREEN_HEIGHT.into() {
-                pos.y -= crate::SCREEN_HEIGHT as f64;
-            }
-            else if pos.y < 0.0 {
-                pos.y += crate::SCREEN_HEIGHT as f64;
-            }
-
-            if utils::is_key_pressed(&key_manager, " "){
-                utils::key_up(key_manager, " ".to_string());
+            if utils::is_key_pressed(key_manager, &InputAction::Fire){
+                utils::key_up(key_manager, InputAction::Fire);
                 must_fire_missile = true;
                 player_pos.x = pos.x;
                 player_pos.y = pos.y;
                 player_pos.rot = pos.rot;
+                player_vel.x = vel.x;
+                player_vel.y = vel.y;
+            }
+
+            if utils::is_key_pressed(key_manager, &InputAction::Hyperspace){
+                utils::key_up(key_manager, InputAction::Hyperspace);
+                if hyperspace::ready(player) {
+                    jumping_ship = Some(entity);
+                }
             }
-            //Update the graphic to reflect the rotation
-            renderable.rot = pos.rot;
         }
     }
 
     if thruster_pushed {
-        ecs.create_entity()
-            .with(components::SoundCue{
-                filename: crate::THRUSTER_FILENAME.to_string(),
-                sc_type: components::SoundCueType::LoopSound
-            })
-            .build();
+        ecs.write_resource::<SoundEvents>().start_loop(Sound::Thruster);
     }
     else{
-        ecs.create_entity()
-            .with(components::SoundCue{
-                filename: crate::THRUSTER_FILENAME.to_string(),
-                sc_type: components::SoundCueType::StopSound
-            })
-            .build();
+        ecs.write_resource::<SoundEvents>().stop(Sound::Thruster);
     }
 
     if must_fire_missile {
-        fire_missile(ecs, player_pos);
+        fire_missile(ecs, player_pos, player_vel);
+    }
+
+    if let Some(ship) = jumping_ship {
+        hyperspace::jump(ecs, ship);
     }
 }
 
 const FRICTION: f64 = 2.5;
 const MAX_SPEED: f64 = 500.0;
-pub fn update_movement(pos: &mut crate::components::Position, player: &mut crate::components::Player, deltaTime: f64){
+// Applies thrust and friction to the ship's velocity, the movement system then moves it
+pub fn update_movement(vel: &mut crate::components::Velocity, player: &mut crate::components::Player, delta_time: f64){
     // player.cur_speed*=FRICTION;
-    player.cur_speed -= player.cur_speed * (FRICTION * deltaTime);
+    vel.x -= vel.x * (FRICTION * delta_time);
+    vel.y -= vel.y * (FRICTION * delta_time);
 
-    player.cur_speed+=player.impulse;
+    vel.x += player.impulse.x;
+    vel.y += player.impulse.y;
 
-    if player.cur_speed.length() > MAX_SPEED {
-        player.cur_speed *= MAX_SPEED/player.cur_speed.length();
+    let speed = vel.speed();
+    if speed > MAX_SPEED {
+        vel.x *= MAX_SPEED/speed;
+        vel.y *= MAX_SPEED/speed;
     }
 
-    pos.x += player.cur_speed.x * deltaTime;
-    pos.y += player.cur_speed.y * deltaTime;
+    player.impulse = vector2d::Vector2D::new(0.0,0.0);
+}
 
-    pos.section = (pos.x as u32/ crate::SECTION_WIDTH) * crate::NO_OF_SECTIONS +  (pos.y as u32 / crate::SECTION_HEIGHT);
+// Moves a position that left the screen back in from the opposite edge
+pub fn wrap_position(pos: &mut components::Position){
+    if pos.x > crate::SCREEN_WIDTH.into() {
+        pos.x -= crate::SCREEN_WIDTH as f64;
+    }
+    else if pos.x < 0.0 {
+        pos.x += crate::SCREEN_WIDTH as f64;
+    }
 
-    player.impulse = vector2d::Vector2D::new(0.0,0.0);
+    if pos.y > crate::SCREEN_HEIGHT.into() {
+        pos.y -= crate::SCREEN_HEIGHT as f64;
+    }
+    else if pos.y < 0.0 {
+        pos.y += crate::SCREEN_HEIGHT as f64;
+    }
+}
+
+// Offset from b to a. When the screen wraps the shorter way round may cross an edge.
+pub fn position_delta(mode: GameMode, a: &components::Position, b: &components::Position) -> (f64, f64){
+    let mut diff_x = a.x - b.x;
+    let mut diff_y = a.y - b.y;
+    if mode.wraps() {
+        let width = SCREEN_WIDTH as f64;
+        let height = SCREEN_HEIGHT as f64;
+        if diff_x > width / 2.0 {
+            diff_x -= width;
+        } else if diff_x < -width / 2.0 {
+            diff_x += width;
+        }
+        if diff_y > height / 2.0 {
+            diff_y -= height;
+        } else if diff_y < -height / 2.0 {
+            diff_y += height;
+        }
+    }
+    (diff_x, diff_y)
+}
+
+// Registers every component and resource, then loads the starting world
+pub fn init_world(ecs: &mut World, seed: u64, mode: GameMode){
+    ecs.register::<components::Position>();
+    ecs.register::<components::PreviousPosition>();
+    ecs.register::<components::Velocity>();
+    ecs.register::<components::AngularVelocity>();
+    ecs.register::<components::Renderable>();
+    ecs.register::<components::Collider>();
+    ecs.register::<components::Player>();
+    ecs.register::<components::Asteroid>();
+    ecs.register::<components::Missile>();
+    ecs.register::<components::Saucer>();
+    ecs.register::<components::Effect>();
+    ecs.register::<components::GameData>();
+    // ecs.register::<components::Star>();
+    ecs.insert(crate::DeltaTime(0.0));
+    ecs.insert(SoundEvents::default());
+    ecs.insert(crate::GameRng::new(seed));
+    ecs.insert(crate::GameStatus::new());
+    ecs.insert(mode);
+    ecs.insert(crate::AsteroidCollisions::default());
+    ecs.insert(crate::HyperspaceRisk::default());
+    ecs.insert(crate::ExtraLifeScore::default());
+    ecs.insert(crate::BestScore::default());
+    ecs.insert(asteroid::AsteroidTiers::default());
+    ecs.insert(wave::Waves::default());
+    ecs.insert(spatial::SpatialGrid::new());
+
+    load_world(ecs);
+}
+
+// The dispatcher has to be set up on the world before use so the collision readers get registered
+pub fn build_dispatcher<'a, 'b>() -> Dispatcher<'a, 'b> {
+    DispatcherBuilder::new() //Creates a dispatcher to run systems
+        .with(movement::Mover, "mover", &[])
+        .with(asteroid::WallBouncer, "wall_bouncer", &["mover"])
+        .with(missile::MissileExpiry, "missile_expiry", &["mover"])
+        .with(saucer::SaucerPilot, "saucer_pilot", &["mover"])
+        .with(hyperspace::HyperspaceDrive, "hyperspace_drive", &["mover"])
+        .with(respawn::ShipRespawner, "ship_respawner", &["hyperspace_drive"])
+        .with(effect::EffectAnimator, "effect_animator", &[])
+        .with(spatial::SpatialGridBuilder, "spatial_grid", &["wall_bouncer", "missile_expiry", "saucer_pilot", "ship_respawner"])
+        .with(collision::CollisionDetector, "collision_detector", &["spatial_grid"])
+        .with(saucer::SaucerCollider::default(), "saucer_collider", &["collision_detector"])
+        .with(asteroid::AsteroidCollider::default(), "asteroid_collider", &["collision_detector"])
+        .with(missile::MissileStriker::default(), "missile_striker", &["collision_detector"])
+        .with(asteroid::AsteroidBouncer::default(), "asteroid_bouncer", &["missile_striker"])
+        .with(extra_life::ExtraLifeAwarder, "extra_life_awarder", &["missile_striker", "saucer_collider"])
+        .build()
+}
+
+pub fn store_previous_positions(ecs: &mut World){
+    let positions = ecs.read_storage::<components::Position>();
+    let mut previous_positions = ecs.write_storage::<components::PreviousPosition>();
+    let entities = ecs.entities();
+    for (pos, entity) in (&positions, &entities).join(){
+        previous_positions.insert(entity, components::PreviousPosition{x: pos.x, y: pos.y}).ok();
+    }
+}
+
+// Blends between the last two ticks for drawing. Entities that just spawned or
+// wrapped around the screen are drawn where they are instead of sliding across it.
+pub fn interpolate_position(pos: &components::Position, previous: Option<&components::PreviousPosition>, alpha: f64) -> (f64, f64){
+    match previous {
+        Some(prev) if (pos.x - prev.x).abs() < SCREEN_WIDTH as f64 / 2.0
+            && (pos.y - prev.y).abs() < SCREEN_HEIGHT as f64 / 2.0 => {
+            (prev.x + (pos.x - prev.x) * alpha, prev.y + (pos.y - prev.y) * alpha)
+        },
+        _ => (pos.x, pos.y)
+    }
+}
+
+// Starts a fresh game from the title or game over screen. The first game plays out
+// from the starting seed; each one after it gets a new seed drawn from the last.
+pub fn start_game(ecs: &mut World){
+    let phase = ecs.read_resource::<crate::GameStatus>().phase;
+    if phase != GamePhase::Title && phase != GamePhase::GameOver {
+        return;
+    }
+
+    if phase == GamePhase::GameOver {
+        let mut game_rng = ecs.write_resource::<crate::GameRng>();
+        let next_seed = game_rng.rng.gen::<u64>();
+        *game_rng = crate::GameRng::new(next_seed);
+    }
+    ecs.delete_all();
+    ecs.maintain();
+    load_world(ecs);
+    ecs.write_resource::<crate::GameStatus>().phase = GamePhase::Playing;
+}
+
+pub fn toggle_pause(ecs: &mut World){
+    {
+        let mut status = ecs.write_resource::<crate::GameStatus>();
+        match status.phase {
+            GamePhase::Playing | GamePhase::LevelTransition => {
+                status.paused_phase = status.phase;
+                status.phase = GamePhase::Paused;
+            },
+            GamePhase::Paused => {
+                status.phase = status.paused_phase;
+                return;
+            },
+            _ => {
+                return;
+            }
+        }
+    }
+    //The looping sounds would otherwise keep playing while paused
+    stop_loops(ecs);
+}
+
+// Silences the thrusters and saucer sirens
+fn stop_loops(ecs: &mut World){
+    let mut sounds = ecs.write_resource::<SoundEvents>();
+    for sound in [Sound::Thruster, Sound::SaucerLarge, Sound::SaucerSmall] {
+        sounds.stop(sound);
+    }
+}
+
+// Outline of the ship sprite pointing up, nose first
+const SHIP_HULL: [(f64, f64); 3] = [(0.0, -25.0), (20.0, 20.0), (-20.0, 20.0)];
+
+pub fn ship_renderable() -> components::Renderable {
+    components::Renderable{
+        tex_name: String::from(assets::SHIP_TEXTURE),
+        i_w: 100,
+        i_h: 100,
+        o_w: 50,
+        o_h: 50,
+        frame: 0,
+        total_frames: 1
+    }
+}
+
+pub fn ship_collider() -> components::Collider {
+    components::Collider::polygon(SHIP_HULL.to_vec(), collision::LAYER_PLAYER, collision::LAYER_ASTEROID | collision::LAYER_SAUCER | collision::LAYER_SAUCER_MISSILE)
 }
 
 // pub const MAX_STARS: u32 = 100;
 
+// Builds the world for a new game, with the RNG started over from the game's seed
 pub fn load_world(ecs: &mut World){
+    let seed = ecs.read_resource::<crate::GameRng>().seed;
+    ecs.insert(crate::GameRng::new(seed));
+    ecs.insert(saucer::SaucerSpawner::new());
+
     ecs.create_entity()
-        .with(crate::components::Position{x: (SCREEN_WIDTH/2) as f64, y: (SCREEN_HEIGHT/2) as f64, rot: 0.0, section: 0})
-        .with(crate:: components::Renderable{
-            tex_name: String::from("img/ship.png"),
-            i_w: 100,
-            i_h: 100,
-            o_w: 50,
-            o_h: 50,
-            frame: 0,
-            total_frames: 1,
-            rot: 0.0
-        })
+        .with(crate::components::Position{x: (SCREEN_WIDTH/2) as f64, y: (SCREEN_HEIGHT/2) as f64, rot: 0.0})
+        .with(ship_renderable())
+        .with(components::Velocity{x: 0.0, y: 0.0})
         .with(crate::components::Player{
             impulse: vector2d::Vector2D::new(0.0,0.0),
-            cur_speed: vector2d::Vector2D::new(0.0,0.0),
             lives: 3,
             died: false,
-            invulnerable: false
+            invulnerable: false,
+            hyperspace: 0.0,
+            hyperspace_cooldown: 0.0,
+            respawning: false,
+            respawn_wait: 0.0,
+            shield: 0.0,
+            life_flash: 0.0
         })
+        .with(ship_collider())
         .build();
 
-    create_asteroid(ecs, components::Position{x: 400.0, y: 235.0, rot: 45.0, section: 0},50);
-
     ecs.create_entity()
         .with(crate::components::GameData{
             score: 0,
             level: 1,
-            showControls: false
+            show_controls: false,
+            extra_lives: 0
         })
         .build();
 
+    wave::spawn_wave(ecs, 1);
+
     // for _ in 0..MAX_STARS { //Create Stars
     //     let mut rng = rand::thread_rng();
     //     let next_x = rng.gen_range(0.0..crate::SCREEN_WIDTH as f64);
@@ -236,58 +395,140 @@ pub fn load_world(ecs: &mut World){
 }
 
 const MAX_MISSILES: usize = 5;
+pub const MISSILE_LIFETIME: f64 = 1.2;
+pub const MISSILE_RADIUS: f64 = 4.0;
+const MISSILE_SPEED: f64 = 600.0;
 
-fn fire_missile(ecs: &mut World, position: components::Position){
+// Missiles leave the ship at MISSILE_SPEED on top of the ship's own velocity
+fn fire_missile(ecs: &mut World, position: components::Position, ship_velocity: components::Velocity){
     {
         let missiles = ecs.read_storage::<crate::components::Missile>();
-        if missiles.count() > MAX_MISSILES - 1{
+        if missiles.join().filter(|missile| !missile.hostile).count() > MAX_MISSILES - 1{
             return;
         }
     }
 
+    let muzzle = components::Velocity::from_heading(position.rot, MISSILE_SPEED);
+    let x = position.x;
+
     ecs.create_entity()
         .with(position)
         .with(crate::components::Renderable{
-            tex_name: String::from("img/missile.png"),
+            tex_name: String::from(assets::MISSILE_TEXTURE),
             i_w: 100,
             i_h: 100,
             o_w: 25,
             o_h: 25,
             frame: 0,
-            total_frames: 1,
-            rot: 0.0
+            total_frames: 1
         })
+        .with(components::Velocity{x: ship_velocity.x + muzzle.x, y: ship_velocity.y + muzzle.y})
         .with(crate::components::Missile{
-            speed: 600.0
+            lifetime: MISSILE_LIFETIME,
+            hostile: false
         })
+        .with(components::Collider::circle(MISSILE_RADIUS, collision::LAYER_MISSILE, collision::LAYER_ASTEROID | collision::LAYER_SAUCER))
         .build();
 
-    ecs.create_entity()
-        .with(components::SoundCue{
-            filename: crate::SHOOT_FILENAME.to_string(),
-            sc_type: components::SoundCueType::PlaySound
-        })
-        .build();
+    ecs.write_resource::<SoundEvents>().play_at(Sound::Shoot, x);
 }
 
-pub fn create_asteroid(ecs: &mut World, position: components::Position, asteroid_size: u32){
-    ecs.create_entity()
+// The asteroid drifts off in the direction of position.rot
+pub fn create_asteroid(ecs: &mut World, position: components::Position, tier: usize) -> Option<specs::Entity> {
+    let parts = {
+        let collisions = *ecs.read_resource::<crate::AsteroidCollisions>();
+        let tiers = ecs.read_resource::<asteroid::AsteroidTiers>();
+        let mut game_rng = ecs.write_resource::<crate::GameRng>();
+        match tiers.get(tier) {
+            Some(asteroid_tier) => asteroid::asteroid_parts(asteroid_tier, tier, position.rot, &mut game_rng.rng, collisions),
+            None => return None
+        }
+    };
+    let (velocity, spin, renderable, asteroid, collider) = parts;
+    let entity = ecs.create_entity()
         .with(position)
-        .with(crate::components::Renderable{
-            tex_name: String::from("img/asteroid1.png"),
-            i_w: 100,
-            i_h: 100,
-            o_w: asteroid_size,
-            o_h: asteroid_size,
-            frame: 0,
-            total_frames: 1,
-            rot: 0.0
-        })
-        .with(crate::components::Asteroid{
-            speed: 150.0,
-            rot_speed: 150.0
-        })
+        .with(velocity)
+        .with(spin)
+        .with(renderable)
+        .with(asteroid)
+        .with(collider)
         .build();
+    Some(entity)
+}
+
+// Swaps in a different tier table, used from the next game started
+pub fn set_asteroid_tiers(ecs: &mut World, tiers: asteroid::AsteroidTiers){
+    ecs.insert(tiers);
+}
+
+// Swaps in a different set of waves, used from the next game started
+pub fn set_waves(ecs: &mut World, waves: wave::Waves){
+    ecs.insert(waves);
+}
+
+// Also updates the asteroids already in the world so the change applies straight away
+pub fn set_asteroid_collisions(ecs: &mut World, enabled: bool){
+    let collisions = crate::AsteroidCollisions(enabled);
+    ecs.insert(collisions);
+
+    let asteroids = ecs.read_storage::<components::Asteroid>();
+    let mut colliders = ecs.write_storage::<components::Collider>();
+    for (_, collider) in (&asteroids, &mut colliders).join(){
+        *collider = asteroid::asteroid_collider(collider.bounding_radius(), collisions);
+    }
+}
+
+pub fn set_hyperspace_risk(ecs: &mut World, percent: u8){
+    ecs.insert(crate::HyperspaceRisk(percent.min(100)));
+}
+
+pub fn apply_settings(ecs: &mut World, settings: crate::GameSettings){
+    set_asteroid_collisions(ecs, settings.asteroid_collisions);
+    set_hyperspace_risk(ecs, settings.hyperspace_risk);
+    ecs.insert(crate::ExtraLifeScore(settings.extra_life_score));
+}
+
+pub fn get_settings(ecs: &World) -> crate::GameSettings {
+    crate::GameSettings {
+        asteroid_collisions: ecs.read_resource::<crate::AsteroidCollisions>().0,
+        hyperspace_risk: ecs.read_resource::<crate::HyperspaceRisk>().0,
+        extra_life_score: ecs.read_resource::<crate::ExtraLifeScore>().0
+    }
+}
+
+// Whether the ship is on screen and under the player's control
+pub fn in_play(player: &components::Player) -> bool {
+    player.hyperspace <= 0.0 && !player.respawning && !player.died
+}
+
+// The respawn shield, or the debug invincibility cheat
+pub fn is_invulnerable(player: &components::Player) -> bool {
+    player.invulnerable || player.shield > 0.0
+}
+
+// Costs the player a life, or ends the game on the last one
+pub fn kill_player(player: &mut components::Player, entity: specs::Entity, entities: &specs::Entities){
+    if is_invulnerable(player) || player.died {
+        return;
+    }
+    if player.lives > 1 {
+        player.died = true;
+    } else {
+        let _ = entities.delete(entity);
+    }
+}
+
+// Every time the score passes a multiple of the extra life score an extra life is earned
+pub fn add_score(gamedata: &mut components::GameData, best: &mut crate::BestScore, points: u32, extra_life: crate::ExtraLifeScore){
+    let before = gamedata.score;
+    gamedata.score += points;
+    //An extra life score of 0 turns extra lives off
+    if let (Some(now), Some(then)) = (gamedata.score.checked_div(extra_life.0), before.checked_div(extra_life.0)) {
+        gamedata.extra_lives += now - then;
+    }
+    if gamedata.score > best.0 {
+        best.0 = gamedata.score;
+    }
 }
 
 pub fn toggle_invincibility(ecs: &mut World){
@@ -298,23 +539,37 @@ pub fn toggle_invincibility(ecs: &mut World){
 }
 
 pub fn create_thousand_asteroids(ecs: &mut World){
-    let mut rng = rand::thread_rng();
-    for i in 0..1000 {
-        let x = rng.gen_range(50.0..crate::SCREEN_WIDTH as f64 - 50.0);
-        let y = rng.gen_range(50.0..crate::SCREEN_HEIGHT as f64 - 50.0);
-        let rot = rng.gen_range(0.0..360.0);
-        create_asteroid(ecs, components::Position{x, y, rot ,section:0}, 50);
+    let tier = ecs.read_resource::<asteroid::AsteroidTiers>().smallest();
+    let mut new_asteroids = Vec::<components::Position>::new();
+    {
+        let mut game_rng = ecs.write_resource::<crate::GameRng>();
+        let rng = &mut game_rng.rng;
+        for _ in 0..1000 {
+            let x = rng.gen_range(50.0..crate::SCREEN_WIDTH as f64 - 50.0);
+            let y = rng.gen_range(50.0..crate::SCREEN_HEIGHT as f64 - 50.0);
+            let rot = rng.gen_range(0.0..360.0);
+            new_asteroids.push(components::Position{x, y, rot});
+        }
+    }
+
+    for new_asteroid in new_asteroids {
+        create_asteroid(ecs, new_asteroid, tier);
     }
 }
 
 pub fn toggle_show_controls(ecs: &mut World){
     let mut gamedatas = ecs.write_storage::<crate::components::GameData>();
     for mut gamedata in (&mut gamedatas).join(){
-        gamedata.showControls = !gamedata.showControls;
+        gamedata.show_controls = !gamedata.show_controls;
     }
 }
 
 pub fn get_asteroid_count(ecs: &World) -> u32{
     let mut asteroids = ecs.read_storage::<crate::components::Asteroid>();
     asteroids.join().count() as u32
-}
\ No newline at end of file
+}
+
+pub fn get_score_and_level(ecs: &World) -> (u32, u32){
+    let gamedatas = ecs.read_storage::<crate::components::GameData>();
+    gamedatas.join().next().map_or((0, 0), |gamedata| (gamedata.score, gamedata.level))
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use sdl2::GameControllerSubsystem;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+
+use asteroids_assignment::input::InputAction;
+
+// Stick and trigger travel (out of 32767) ignored before they count as pressed
+const STICK_DEADZONE: i16 = 8000;
+const TRIGGER_THRESHOLD: i16 = 8000;
+
+// Turns SDL game controller events into the same actions the keyboard bindings produce
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    controllers: HashMap<u32, GameController>, //Open controllers by joystick id
+    stick_direction: i8, //-1 left, 0 centred, 1 right
+    stick_amount: f64, //How far past the deadzone the stick is pushed, 0 to 1
+    trigger_thrust: bool
+}
+
+impl Gamepads {
+    pub fn new(subsystem: GameControllerSubsystem) -> Self {
+        Gamepads {
+            subsystem,
+            controllers: HashMap::new(),
+            stick_direction: 0,
+            stick_amount: 0.0,
+            trigger_thrust: false
+        }
+    }
+
+    // Returns the actions pressed (true) or released (false) by the event.
+    // SDL also sends a device added event for every controller plugged in at startup.
+    pub fn handle_event(&mut self, event: &Event) -> Vec<(InputAction, bool)> {
+        let mut actions = Vec::new();
+        match event {
+            Event::ControllerDeviceAdded {which,..} => {
+                match self.subsystem.open(*which) {
+                    Ok(controller) => {
+                        println!("Controller connected: {}", controller.name());
+                        self.controllers.insert(controller.instance_id(), controller);
+                    },
+                    Err(e) => {
+                        println!("Failed to open controller: {}", e);
+                    }
+                }
+            },
+            Event::ControllerDeviceRemoved {which,..} => {
+                if let Some(controller) = self.controllers.remove(which) {
+                    println!("Controller disconnected: {}", controller.name());
+                }
+                // Let go of anything the controller was holding
+                self.set_stick_direction(0, &mut actions);
+                self.set_trigger_thrust(false, &mut actions);
+            },
+            Event::ControllerAxisMotion {axis: Axis::LeftX, value,..} => {
+                self.stick_amount = ((value.unsigned_abs() as f64 - STICK_DEADZONE as f64) / (i16::MAX as f64 - STICK_DEADZONE as f64)).clamp(0.0, 1.0);
+                let direction = if *value < -STICK_DEADZONE {
+                    -1
+                } else if *value > STICK_DEADZONE {
+                    1
+                } else {
+                    0
+                };
+                self.set_stick_direction(direction, &mut actions);
+            },
+            Event::ControllerAxisMotion {axis: Axis::TriggerRight, value,..} => {
+                self.set_trigger_thrust(*value > TRIGGER_THRESHOLD, &mut actions);
+            },
+            Event::ControllerButtonDown {button,..} => {
+                if let Some(action) = button_action(*button) {
+                    actions.push((action, true));
+                }
+            },
+            Event::ControllerButtonUp {button,..} => {
+                if let Some(action) = button_action(*button) {
+                    actions.push((action, false));
+                }
+            },
+            _ => {}
+        }
+        actions
+    }
+
+    // Share of the full turning speed the stick asks for; full speed when it isn't
+    // turning, so the keys and d-pad still turn at the usual rate
+    pub fn rotate_amount(&self) -> f64 {
+        if self.stick_direction == 0 { 1.0 } else { self.stick_amount }
+    }
+
+    fn set_stick_direction(&mut self, direction: i8, actions: &mut Vec<(InputAction, bool)>) {
+        if direction == self.stick_direction {
+            return;
+        }
+        match self.stick_direction {
+            -1 => actions.push((InputAction::RotateLeft, false)),
+            1 => actions.push((InputAction::RotateRight, false)),
+            _ => {}
+        }
+        match direction {
+            -1 => actions.push((InputAction::RotateLeft, true)),
+            1 => actions.push((InputAction::RotateRight, true)),
+            _ => {}
+        }
+        self.stick_direction = direction;
+    }
+
+    fn set_trigger_thrust(&mut self, thrust: bool, actions: &mut Vec<(InputAction, bool)>) {
+        if thrust != self.trigger_thrust {
+            actions.push((InputAction::Thrust, thrust));
+            self.trigger_thrust = thrust;
+        }
+    }
+}
+
+fn button_action(button: Button) -> Option<InputAction> {
+    match button {
+        Button::A | Button::RightShoulder => Some(InputAction::Fire),
+        Button::B | Button::LeftShoulder => Some(InputAction::Hyperspace),
+        Button::DPadUp => Some(InputAction::Thrust),
+        Button::DPadLeft => Some(InputAction::RotateLeft),
+        Button::DPadRight => Some(InputAction::RotateRight),
+        Button::X => Some(InputAction::Options),
+        Button::Y => Some(InputAction::ToggleMusic),
+        Button::Start => Some(InputAction::Pause),
+        Button::Back => Some(InputAction::ToggleControls),
+        _ => None
+    }
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use specs::{World, WorldExt, Join};
+
+use crate::{components, utils, Simulation};
+use crate::input::{ActionState, InputAction};
+use crate::sound::SoundEvents;
+
+pub const DEFAULT_TICKS: u64 = 7200;
+pub const DEFAULT_DELTA_TIME: f64 = crate::FIXED_TIMESTEP;
+
+// An action press or release applied right before the given tick is simulated
+pub struct ScriptedKey {
+    pub tick: u64,
+    pub action: InputAction,
+    pub pressed: bool
+}
+
+#[derive(Debug, PartialEq)]
+pub struct HeadlessReport {
+    pub seed: u64,
+    pub ticks: u64,
+    pub score: u32,
+    pub level: u32,
+    pub lives: u32,
+    pub game_over: bool
+}
+
+// Steps the same world as the windowed game without SDL or audio. The simulation
+// comes in with its settings and asteroid tiers already applied.
+pub fn run(mut sim: Simulation, ticks: u64, delta_time: f64, script: &[ScriptedKey]) -> HeadlessReport {
+    sim.start_game(); //Skip the title screen
+    simulate(sim, ticks, delta_time, script)
+}
+
+// Plays a simulation made with Simulation::from_replay to the replay's last tick
+pub fn run_replay(sim: Simulation) -> HeadlessReport {
+    let ticks = sim.replay_length();
+    simulate(sim, ticks, crate::FIXED_TIMESTEP, &[])
+}
+
+fn simulate(mut sim: Simulation, ticks: u64, delta_time: f64, script: &[ScriptedKey]) -> HeadlessReport {
+    let mut key_manager = ActionState::new();
+
+    for tick in 0..ticks {
+        for scripted in script.iter().filter(|s| s.tick == tick) {
+            if scripted.pressed {
+                utils::key_down(&mut key_manager, scripted.action);
+            } else {
+                utils::key_up(&mut key_manager, scripted.action);
+            }
+        }
+
+        sim.step(&mut key_manager, delta_time);
+        //Nothing plays the sounds here, so drop them before they pile up
+        sim.ecs.write_resource::<SoundEvents>().drain();
+    }
+
+    //Read afterwards, as a game started after a game over gets a seed of its own
+    report(&sim.ecs, sim.seed(), ticks)
+}
+
+pub(crate) fn report(ecs: &World, seed: u64, ticks: u64) -> HeadlessReport {
+    let mut report = HeadlessReport{seed, ticks, score: 0, level: 0, lives: 0, game_over: ecs.read_resource::<crate::GameStatus>().phase == crate::GamePhase::GameOver};
+
+    let gamedatas = ecs.read_storage::<components::GameData>();
+    for gamedata in gamedatas.join(){
+        report.score = gamedata.score;
+        report.level = gamedata.level;
+    }
+
+    let players = ecs.read_storage::<components::Player>();
+    for player in players.join(){
+        report.lives = player.lives;
+    }
+
+    report
+}
+
+// Script lines look like "<tick> <action> <down|up>", e.g. "120 Thrust down".
+// Only the held actions (Thrust, RotateLeft, RotateRight, Fire, Hyperspace) have an effect.
+// Empty lines and lines starting with '#' are ignored.
+pub fn load_script(path: &str) -> Result<Vec<ScriptedKey>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut script = Vec::new();
+
+    for (line_no, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 3 {
+            return Err(format!("{}:{}: expected \"<tick> <action> <down|up>\"", path, line_no + 1));
+        }
+
+        let tick = parts[0].parse::<u64>()
+            .map_err(|e| format!("{}:{}: invalid tick: {}", path, line_no + 1, e))?;
+        let action = parse_action(parts[1])
+            .ok_or(format!("{}:{}: unknown action \"{}\"", path, line_no + 1, parts[1]))?;
+        let pressed = match parts[2] {
+            "down" => true,
+            "up" => false,
+            other => return Err(format!("{}:{}: unknown key state \"{}\"", path, line_no + 1, other))
+        };
+
+        script.push(ScriptedKey{tick, action, pressed});
+    }
+
+    Ok(script)
+}
+
+fn parse_action(name: &str) -> Option<InputAction> {
+    match name {
+        "Thrust" => Some(InputAction::Thrust),
+        "RotateLeft" => Some(InputAction::RotateLeft),
+        "RotateRight" => Some(InputAction::RotateRight),
+        "Fire" => Some(InputAction::Fire),
+        "Hyperspace" => Some(InputAction::Hyperspace),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameMode, GamePhase, GameStatus};
+
+    // Turns left the whole time and taps fire every quarter second
+    fn script() -> Vec<ScriptedKey> {
+        let mut script = vec![ScriptedKey{tick: 0, action: InputAction::RotateLeft, pressed: true}];
+        for tap in 0..100 {
+            script.push(ScriptedKey{tick: tap * 30, action: InputAction::Fire, pressed: true});
+            script.push(ScriptedKey{tick: tap * 30 + 15, action: InputAction::Fire, pressed: false});
+        }
+        script
+    }
+
+    #[test]
+    fn fixed_seed_gives_known_result() {
+        let report = run(Simulation::new(42, GameMode::Bounce), 3600, DEFAULT_DELTA_TIME, &script());
+        assert_eq!(report, HeadlessReport{seed: 42, ticks: 3600, score: 50, level: 1, lives: 2, game_over: false});
+    }
+
+    #[test]
+    fn same_seed_gives_same_result() {
+        let first = run(Simulation::new(7, GameMode::Classic), 3600, DEFAULT_DELTA_TIME, &script());
+        let second = run(Simulation::new(7, GameMode::Classic), 3600, DEFAULT_DELTA_TIME, &script());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn setting_the_tables_does_not_change_the_game() {
+        let plain = run(Simulation::new(42, GameMode::Bounce), 3600, DEFAULT_DELTA_TIME, &script());
+
+        //The way main sets up a game, with the tables loaded from file
+        let mut sim = Simulation::new(42, GameMode::Bounce);
+        sim.set_asteroid_tiers(sim.asteroid_tiers());
+        sim.set_waves(sim.waves());
+        let configured = run(sim, 3600, DEFAULT_DELTA_TIME, &script());
+        assert_eq!(plain, configured);
+    }
+
+    #[test]
+    fn next_game_plays_out_from_its_own_seed() {
+        let sim = Simulation::new(5, GameMode::Classic);
+        sim.ecs.write_resource::<GameStatus>().phase = GamePhase::GameOver; //As if the first game just ended
+        let second = run(sim, 3600, DEFAULT_DELTA_TIME, &script());
+        assert_ne!(second.seed, 5);
+
+        //The seed saved with a high score has to bring the same game back
+        let replayed = run(Simulation::new(second.seed, GameMode::Classic), 3600, DEFAULT_DELTA_TIME, &script());
+        assert_eq!(second, replayed);
+    }
+}
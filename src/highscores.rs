@@ -0,0 +1,155 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+pub const MAX_ENTRIES: usize = 10;
+pub const HIGHSCORES_FILENAME: &str = "highscores.toml";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub initials: String,
+    pub score: u32,
+    pub level: u32,
+    pub date: String, //YYYY-MM-DD
+    pub seed: u64
+}
+
+// Best scores first, at most MAX_ENTRIES long
+#[derive(Default, Serialize, Deserialize)]
+pub struct HighScoreTable {
+    pub entries: Vec<HighScore>
+}
+
+impl HighScoreTable {
+    // A missing file is an empty table, not an error
+    pub fn load(path: &Path) -> Result<HighScoreTable, String> {
+        if !path.exists() {
+            return Ok(HighScoreTable::default());
+        }
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut table: HighScoreTable = toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        table.entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        table.entries.truncate(MAX_ENTRIES);
+        Ok(table)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let contents = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.entries.len() < MAX_ENTRIES || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    // Returns the rank (0 = best) the entry landed on, if it made the table
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        // Ties go below the scores that got there first
+        let rank = self.entries.iter().position(|existing| entry.score > existing.score).unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    pub fn best(&self) -> u32 {
+        self.entries.first().map_or(0, |entry| entry.score)
+    }
+}
+
+// Per-user data directory for the game, following each platform's convention
+pub fn data_dir() -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    base.unwrap_or_else(|| PathBuf::from(".")).join("asteroids_assignment")
+}
+
+pub fn default_path() -> PathBuf {
+    data_dir().join(HIGHSCORES_FILENAME)
+}
+
+// Today's date in UTC as YYYY-MM-DD
+pub fn today() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Days since 1970-01-01 to a calendar date (Howard Hinnant's civil_from_days)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(initials: &str, score: u32) -> HighScore {
+        HighScore{initials: initials.to_string(), score, level: 1, date: "2024-01-01".to_string(), seed: 0}
+    }
+
+    // A full table scoring 1000, 900 ... 100
+    fn full_table() -> HighScoreTable {
+        HighScoreTable{entries: (0..MAX_ENTRIES as u32).map(|i| entry("AAA", 1000 - i * 100)).collect()}
+    }
+
+    #[test]
+    fn full_table_only_takes_scores_above_the_last() {
+        let mut table = full_table();
+        assert!(!table.qualifies(100)); //Only ties the last entry
+        assert!(!table.qualifies(50));
+        assert!(table.qualifies(101));
+
+        assert_eq!(table.insert(entry("LOW", 100)), None);
+        assert_eq!(table.insert(entry("NEW", 550)), Some(5));
+        assert_eq!(table.entries.len(), MAX_ENTRIES);
+        assert_eq!(table.entries.last().unwrap().score, 200); //The old 100 dropped off
+    }
+
+    #[test]
+    fn zero_never_qualifies() {
+        assert!(!HighScoreTable::default().qualifies(0));
+    }
+
+    #[test]
+    fn ties_go_below_the_older_score() {
+        let mut table = HighScoreTable::default();
+        table.insert(entry("OLD", 500));
+        table.insert(entry("TOP", 800));
+        assert_eq!(table.insert(entry("NEW", 500)), Some(2));
+        let initials: Vec<&str> = table.entries.iter().map(|e| e.initials.as_str()).collect();
+        assert_eq!(initials, ["TOP", "OLD", "NEW"]);
+        assert_eq!(table.best(), 800);
+    }
+
+    #[test]
+    fn days_convert_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19723), (2024, 1, 1));
+    }
+}
@@ -0,0 +1,108 @@
+use specs::prelude::*;
+use specs::{World, WorldExt, Entities, Join};
+
+use rand::Rng;
+
+use crate::{components, effect, game, GameRng, HyperspaceRisk, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::sound::{Sound, SoundEvents};
+
+pub const HYPERSPACE_TIME: f64 = 0.8; //Seconds the ship is gone for
+pub const HYPERSPACE_COOLDOWN: f64 = 3.0; //Seconds after re-entry before the next jump
+const EFFECT_SIZE: u32 = 120;
+const ENTRY_MARGIN: f64 = 100.0; //Keeps the ship from re-entering right on the edge
+
+// Whether the player can jump right now
+pub fn ready(player: &components::Player) -> bool {
+    player.hyperspace <= 0.0 && player.hyperspace_cooldown <= 0.0
+}
+
+// Takes the ship out of normal space: it stops, disappears and can't be hit until it re-enters
+pub fn jump(ecs: &mut World, ship: Entity){
+    let position = {
+        let mut players = ecs.write_storage::<components::Player>();
+        let mut velocities = ecs.write_storage::<components::Velocity>();
+        let positions = ecs.read_storage::<components::Position>();
+        let (player, vel, pos) = match (players.get_mut(ship), velocities.get_mut(ship), positions.get(ship)) {
+            (Some(player), Some(vel), Some(pos)) => (player, vel, pos),
+            _ => return
+        };
+        player.hyperspace = HYPERSPACE_TIME;
+        player.hyperspace_cooldown = HYPERSPACE_TIME + HYPERSPACE_COOLDOWN;
+        player.impulse = vector2d::Vector2D::new(0.0,0.0);
+        vel.x = 0.0;
+        vel.y = 0.0;
+        components::Position{x: pos.x, y: pos.y, rot: 0.0}
+    };
+
+    ecs.write_storage::<components::Renderable>().remove(ship);
+    ecs.write_storage::<components::Collider>().remove(ship);
+    ecs.write_resource::<SoundEvents>().play_at(Sound::Hyperspace, position.x);
+
+    ecs.create_entity()
+        .with(position)
+        .with(effect::ring(EFFECT_SIZE))
+        .with(effect::ring_effect())
+        .build();
+}
+
+// Counts down the jump and cooldown, and brings the ship back out somewhere random.
+// Re-entry can go wrong and destroy the ship, depending on the HyperspaceRisk.
+pub struct HyperspaceDrive;
+
+impl<'a> System<'a> for HyperspaceDrive {
+    type SystemData = (
+        WriteStorage<'a, components::Player>,
+        WriteStorage<'a, components::Position>,
+        WriteStorage<'a, components::Renderable>,
+        WriteStorage<'a, components::Collider>,
+        WriteStorage<'a, components::Effect>,
+        Write<'a, SoundEvents>,
+        Entities<'a>,
+        WriteExpect<'a, GameRng>,
+        Read<'a, HyperspaceRisk>,
+        Read<'a, crate::DeltaTime>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut players, mut positions, mut renderables, mut colliders, mut effects, mut sounds, entities, mut game_rng, risk, deltatime) = data;
+        let deltatime = deltatime.0;
+        let rng = &mut game_rng.rng;
+        let mut arrivals = Vec::<(components::Position, bool)>::new();
+
+        for (player, entity) in (&mut players, &entities).join() {
+            player.hyperspace_cooldown = (player.hyperspace_cooldown - deltatime).max(0.0);
+            if player.hyperspace <= 0.0 {
+                continue;
+            }
+            player.hyperspace -= deltatime;
+            if player.hyperspace > 0.0 {
+                continue;
+            }
+            player.hyperspace = 0.0;
+
+            let x = rng.gen_range(ENTRY_MARGIN..SCREEN_WIDTH as f64 - ENTRY_MARGIN);
+            let y = rng.gen_range(ENTRY_MARGIN..SCREEN_HEIGHT as f64 - ENTRY_MARGIN);
+            let exploded = rng.gen_range(0..100) < risk.0;
+            if let Some(pos) = positions.get_mut(entity) {
+                pos.x = x;
+                pos.y = y;
+            }
+            renderables.insert(entity, game::ship_renderable()).ok();
+            colliders.insert(entity, game::ship_collider()).ok();
+
+            if exploded {
+                game::kill_player(player, entity, &entities);
+            }
+            arrivals.push((components::Position{x, y, rot: 0.0}, exploded));
+        }
+
+        for (position, exploded) in arrivals {
+            sounds.play_at(if exploded { Sound::ExplosionBig } else { Sound::Hyperspace }, position.x);
+
+            let ring = entities.create();
+            positions.insert(ring, position).ok();
+            renderables.insert(ring, effect::ring(EFFECT_SIZE)).ok();
+            effects.insert(ring, effect::ring_effect()).ok();
+        }
+    }
+}
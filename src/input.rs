@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+pub const BINDINGS_FILENAME: &str = "controls.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum InputAction {
+    Thrust,
+    RotateLeft,
+    RotateRight,
+    Fire,
+    Hyperspace,
+    Pause, //Also starts a game from the title and game over screens
+    ToggleMusic,
+    ToggleFpsLock,
+    ToggleInvincibility,
+    ThousandAsteroids,
+    ToggleControls,
+    Options,
+    Quit
+}
+
+impl InputAction {
+    // Actions the game reads every tick while held; the rest fire once when released
+    pub fn is_held(&self) -> bool {
+        matches!(self, InputAction::Thrust | InputAction::RotateLeft | InputAction::RotateRight | InputAction::Fire | InputAction::Hyperspace)
+    }
+}
+
+pub const ROTATE_STEPS: u8 = 8; //Analog turning is rounded to eighths, so a replay can keep it in three bits
+
+// Which gameplay actions are currently held, filled in by the event loop, and how
+// hard an analog stick is turning the ship
+#[derive(Clone, Default)]
+pub struct ActionState {
+    pub held: HashMap<InputAction, bool>,
+    pub rotate_steps: u8 //Eighths of the full turning speed, 0 for full speed as with the keys
+}
+
+impl ActionState {
+    pub fn new() -> Self {
+        ActionState::default()
+    }
+
+    // Share of the full turning speed the ship turns at
+    pub fn rotate_amount(&self) -> f64 {
+        match self.rotate_steps {
+            0 => 1.0,
+            steps => steps as f64 / ROTATE_STEPS as f64
+        }
+    }
+
+    // Rounds to the nearest step, but never down to not turning at all
+    pub fn set_rotate_amount(&mut self, amount: f64) {
+        let steps = (amount.clamp(0.0, 1.0) * ROTATE_STEPS as f64).round() as u8;
+        self.rotate_steps = if steps >= ROTATE_STEPS { 0 } else { steps.max(1) };
+    }
+}
+
+// Maps key names (as SDL names them, e.g. "W", "Space", "Left") to actions
+pub struct Bindings {
+    keys: HashMap<String, InputAction>
+}
+
+impl Bindings {
+    pub fn action_for(&self, key_name: &str) -> Option<InputAction> {
+        self.keys.get(key_name).copied()
+    }
+
+    // First key bound to the action, for showing in the controls overlay
+    pub fn key_for(&self, action: InputAction) -> String {
+        let mut keys: Vec<&String> = self.keys.iter()
+            .filter(|(_, bound)| **bound == action)
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        keys.first().map_or("?".to_string(), |key| key.to_string())
+    }
+
+    pub fn key_names(&self) -> impl Iterator<Item = &String> {
+        self.keys.keys()
+    }
+
+    // Parses a table of action = [keys], e.g. Thrust = ["W", "Up"].
+    // Actions left out of the file keep their default keys.
+    pub fn from_toml(contents: &str) -> Result<Bindings, String> {
+        let table: HashMap<InputAction, Vec<String>> = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+        let mut bindings = Bindings::default();
+        bindings.keys.retain(|_, action| !table.contains_key(action));
+        for (action, keys) in &table {
+            for key in keys {
+                // A key from the file replaces a default binding, but not another line of the file
+                if let Some(existing) = bindings.keys.insert(key.to_string(), *action) {
+                    if existing != *action && table.contains_key(&existing) {
+                        return Err(format!("Key \"{}\" is bound to both {:?} and {:?}", key, existing, action));
+                    }
+                }
+            }
+        }
+        Ok(bindings)
+    }
+
+    // Loads the bindings file if there is one, otherwise uses the default layout
+    pub fn load(path: &str) -> Result<Bindings, String> {
+        if !Path::new(path).exists() {
+            return Ok(Bindings::default());
+        }
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Bindings::from_toml(&contents).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let defaults = [
+            ("W", InputAction::Thrust),
+            ("A", InputAction::RotateLeft),
+            ("D", InputAction::RotateRight),
+            ("Space", InputAction::Fire),
+            ("S", InputAction::Hyperspace),
+            ("Return", InputAction::Pause),
+            ("P", InputAction::ToggleMusic),
+            ("U", InputAction::ToggleFpsLock),
+            ("I", InputAction::ToggleInvincibility),
+            ("O", InputAction::ThousandAsteroids),
+            ("H", InputAction::ToggleControls),
+            ("M", InputAction::Options),
+            ("Escape", InputAction::Quit)
+        ];
+
+        Bindings {
+            keys: defaults.iter().map(|(key, action)| (key.to_string(), *action)).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_controls_match_the_defaults() {
+        let bindings = Bindings::from_toml(include_str!("../controls.toml")).unwrap();
+        let defaults = Bindings::default();
+        assert_eq!(bindings.keys, defaults.keys);
+    }
+
+    #[test]
+    fn file_overrides_only_the_actions_it_lists() {
+        let bindings = Bindings::from_toml("Thrust = [\"Up\", \"K\"]\n").unwrap();
+        assert_eq!(bindings.action_for("Up"), Some(InputAction::Thrust));
+        assert_eq!(bindings.action_for("K"), Some(InputAction::Thrust));
+        assert_eq!(bindings.action_for("W"), None); //The default key is dropped
+        assert_eq!(bindings.key_for(InputAction::Thrust), "K");
+        assert_eq!(bindings.action_for("Space"), Some(InputAction::Fire));
+    }
+
+    #[test]
+    fn file_can_take_a_default_key_from_another_action() {
+        let bindings = Bindings::from_toml("Thrust = [\"Space\"]\n").unwrap();
+        assert_eq!(bindings.action_for("Space"), Some(InputAction::Thrust));
+        assert_eq!(bindings.key_for(InputAction::Fire), "?");
+    }
+
+    #[test]
+    fn key_bound_twice_in_the_file_is_refused() {
+        let error = Bindings::from_toml("Thrust = [\"X\"]\nFire = [\"X\"]\n").err().unwrap();
+        assert!(error.starts_with("Key \"X\" is bound to both"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_action_is_refused() {
+        let error = Bindings::from_toml("Jump = [\"J\"]\n").err().unwrap();
+        assert!(error.contains("unknown variant `Jump`"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_key_names_are_kept_for_checking() {
+        //Only SDL knows the key names, so the game checks key_names once it has started
+        let bindings = Bindings::from_toml("Fire = [\"Wibble\"]\n").unwrap();
+        assert!(bindings.key_names().any(|name| name == "Wibble"));
+    }
+}
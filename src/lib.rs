@@ -0,0 +1,175 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+pub mod utils;
+pub mod assets;
+pub mod input;
+pub mod components;
+pub mod game;
+pub mod asteroid;
+pub mod missile;
+pub mod saucer;
+pub mod hyperspace;
+pub mod respawn;
+pub mod extra_life;
+pub mod effect;
+pub mod wave;
+pub mod sound;
+pub mod sound_manager;
+pub mod simulation;
+pub mod replay;
+pub mod headless;
+pub mod highscores;
+pub mod settings;
+pub mod movement;
+pub mod spatial;
+pub mod collision;
+
+pub use simulation::Simulation;
+
+pub const SCREEN_WIDTH: u32 = 1920;
+pub const SCREEN_HEIGHT: u32 = 1080;
+
+// Every system runs at this fixed rate, independent of the render frame rate
+pub const FIXED_TIMESTEP: f64 = 1.0 / 120.0;
+// Longest frame the simulation will catch up on, so a stall doesn't snowball
+pub const MAX_FRAME_TIME: f64 = 0.25;
+
+pub const MUSIC_FILENAME: &str = "sounds/music/space_ranger.wav";
+pub const THRUSTER_FILENAME: &str = "sounds/fx/thrusters.mp3";
+pub const SHOOT_FILENAME: &str = "sounds/fx/shoot.mp3";
+pub const RELOAD_FILENAME: &str = "sounds/fx/reload.wav";
+pub const SAUCER_LARGE_FILENAME: &str = "sounds/fx/saucer_big.wav";
+pub const SAUCER_SMALL_FILENAME: &str = "sounds/fx/saucer_small.wav";
+pub const HYPERSPACE_FILENAME: &str = "sounds/fx/hyperspace.wav";
+pub const EXTRA_LIFE_FILENAME: &str = "sounds/fx/extra_life.wav";
+pub const MENU_FILENAME: &str = "sounds/fx/menu.wav";
+
+#[derive(Default)]
+pub struct DeltaTime(pub f64);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GamePhase {
+    Title,
+    Playing,
+    Paused,
+    LevelTransition, //Field cleared, waiting before the next wave
+    GameOver
+}
+
+// Which phase the game is in; systems only run while Playing or in a LevelTransition
+pub struct GameStatus {
+    pub phase: GamePhase,
+    pub paused_phase: GamePhase, //Phase to go back to when unpausing
+    pub transition_time: f64 //Seconds left before the next wave spawns
+}
+
+impl GameStatus {
+    pub fn new() -> Self {
+        GameStatus {
+            phase: GamePhase::Title,
+            paused_phase: GamePhase::Playing,
+            transition_time: 0.0
+        }
+    }
+
+    pub fn runs_systems(&self) -> bool {
+        self.phase == GamePhase::Playing || self.phase == GamePhase::LevelTransition
+    }
+}
+
+impl Default for GameStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// How asteroids and missiles treat the screen edges; the player always wraps
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GameMode {
+    #[default]
+    Bounce, //Asteroids bounce off the edges and missiles fly off screen
+    Classic //Everything wraps around like the arcade original
+}
+
+impl GameMode {
+    pub fn wraps(&self) -> bool {
+        *self == GameMode::Classic
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Bounce => "bounce",
+            GameMode::Classic => "classic"
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<GameMode,String> {
+        match name.to_lowercase().as_str() {
+            "bounce" => Ok(GameMode::Bounce),
+            "classic" => Ok(GameMode::Classic),
+            _ => Err(format!("Unknown game mode '{}', expected bounce or classic", name))
+        }
+    }
+}
+
+// Whether asteroids bounce off each other instead of passing through
+#[derive(Default, Clone, Copy)]
+pub struct AsteroidCollisions(pub bool);
+
+// Percentage chance of the ship blowing up as it comes back out of hyperspace
+#[derive(Clone, Copy)]
+pub struct HyperspaceRisk(pub u8);
+
+impl Default for HyperspaceRisk {
+    fn default() -> Self {
+        HyperspaceRisk(10)
+    }
+}
+
+// Points between extra lives, 0 for none
+#[derive(Clone, Copy)]
+pub struct ExtraLifeScore(pub u32);
+
+impl Default for ExtraLifeScore {
+    fn default() -> Self {
+        ExtraLifeScore(10_000)
+    }
+}
+
+// Options that change how the game plays, picked on the command line and kept in replays
+#[derive(Clone, Copy)]
+pub struct GameSettings {
+    pub asteroid_collisions: bool,
+    pub hyperspace_risk: u8, //Percent
+    pub extra_life_score: u32
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            asteroid_collisions: AsteroidCollisions::default().0,
+            hyperspace_risk: HyperspaceRisk::default().0,
+            extra_life_score: ExtraLifeScore::default().0
+        }
+    }
+}
+
+// Seeded RNG shared by all spawning code so a run can be reproduced from its seed
+pub struct GameRng {
+    pub seed: u64,
+    pub rng: StdRng
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed)
+        }
+    }
+}
+
+// Highest score seen, from the high score table or the games played since
+#[derive(Default, Clone, Copy)]
+pub struct BestScore(pub u32);
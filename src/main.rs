@@ -1,51 +1,70 @@
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::render::{WindowCanvas, TextureCreator};
+use sdl2::render::{WindowCanvas, Texture, TextureCreator};
+use sdl2::surface::Surface;
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::video::WindowContext;
 use sdl2::pixels::Color;
 use sdl2::rect::{Rect,Point};
-use specs::{World, WorldExt, Join, DispatcherBuilder};
+use specs::{World, WorldExt, Join, LendJoin};
 use std::time::Instant;
 
-use std::sync::Mutex;
 use std::time::Duration;
 use std::path::Path;
-use std::collections::HashMap;
-use kira::track::effect::delay::DelayBuilder;
-
-use once_cell::sync::Lazy;
-
-pub mod texture_manager;
-pub mod sound_manager;
-pub mod utils;
-pub mod components;
-pub mod game;
-pub mod asteroid;
-pub mod missile;
+use std::rc::Rc;
+
+use asteroids_assignment::{components, extra_life, game, headless, respawn, utils, Simulation};
+use asteroids_assignment::assets::{self, AssetKind, AssetProblem};
+use asteroids_assignment::replay::Replay;
+use asteroids_assignment::asteroid::{AsteroidTiers, ASTEROIDS_FILENAME};
+use asteroids_assignment::wave::{self, Waves, WAVES_FILENAME};
+use asteroids_assignment::sound::{Sound, SoundEvent, SoundEvents};
+use asteroids_assignment::settings::{self, Settings};
+use options_menu::MenuResult;
+use asteroids_assignment::sound_manager::{self, SoundManager};
+use asteroids_assignment::input::{ActionState, Bindings, InputAction, BINDINGS_FILENAME};
+use asteroids_assignment::highscores::{self, HighScore, HighScoreTable};
+use asteroids_assignment::{SCREEN_WIDTH, SCREEN_HEIGHT, BestScore, GameMode, GamePhase, GameStatus, GameSettings, HyperspaceRisk, ExtraLifeScore};
+
+mod texture_manager;
+mod gamepad;
+mod options_menu;
+
+const GAME_OVER_MIN_DURATION: Duration = Duration::from_secs(1);
+const PLACEHOLDER_WIDTH: u32 = 400; //Wide enough for every frame of a sprite sheet
+const PLACEHOLDER_HEIGHT: u32 = 100;
+const PLACEHOLDER_SQUARE: u32 = 25;
 
 // const IMG_WIDTH: u32 = 1000;
 // const IMG_HEIGHT: u32 = 1000;
 // const OUTPUT_WIDTH: u32 = 100;
 // const OUTPUT_HEIGHT: u32 = 100;
-const SCREEN_WIDTH: u32 = 1920;
-const SCREEN_HEIGHT: u32 = 1080;
-const NO_OF_SECTIONS: u32 = 4;
-const SECTION_WIDTH: u32 = SCREEN_WIDTH/NO_OF_SECTIONS;
-const SECTION_HEIGHT: u32 = SCREEN_HEIGHT/NO_OF_SECTIONS;
-
-const MUSIC_FILENAME: &str = "sounds/music/space_ranger.wav";
-const THRUSTER_FILENAME: &str = "sounds/fx/thrusters.mp3";
-const SHOOT_FILENAME: &str = "sounds/fx/shoot.mp3";
-const RELOAD_FILENAME: &str = "sounds/fx/reload.wav";
 
-#[derive(Default)]
-pub struct DeltaTime(pub f64);
+// Everything drawn over the game that doesn't come from the world
+struct Overlay<'a> {
+    bindings: &'a Bindings,
+    highscore_table: &'a HighScoreTable,
+    initials: Option<&'a str>, //Being typed in on the game over screen
+    options: Option<&'a [String]>, //The sound options menu, when it is open
+    fps: u64
+}
 
-fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::TextureManager<WindowContext>, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, ecs: &World, fps: u64) -> Result<(),String> {
+fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::TextureManager<WindowContext>, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, ecs: &World, overlay: Overlay, alpha: f64) -> Result<(),String> {
+    let Overlay{bindings, highscore_table, initials, options, fps} = overlay;
     let color = Color::RGB(255,255,255);
     canvas.set_draw_color(color);
     canvas.clear();
 
+    let phase = ecs.read_resource::<GameStatus>().phase;
+    if phase == GamePhase::Title {
+        match options {
+            Some(lines) => render_options(canvas, texture_creator, font, bindings, lines)?,
+            None => render_title(canvas, texture_creator, font, bindings, highscore_table)?
+        }
+        canvas.present();
+        return Ok(());
+    }
+
     // // Draw Greeting
     // let hello_text: String = "Hello World".to_string();
     // let surface = _font
@@ -103,34 +122,52 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
     }
 
     let renderables = ecs.read_storage::<components::Renderable>();
+    let previous_positions = ecs.read_storage::<components::PreviousPosition>();
+    let players = ecs.read_storage::<components::Player>();
+    let entities = ecs.entities();
+    let wraps = ecs.read_resource::<GameMode>().wraps();
 
-    for(renderable, pos) in (&renderables,&positions).join(){
-        let src = Rect::new(0,0,renderable.i_w,renderable.i_h);
-        let x = pos.x as i32;
-        let y = pos.y as i32;
+    for(renderable, pos, prev, entity) in (&renderables,&positions,(&previous_positions).maybe(),&entities).join(){
+        if players.get(entity).is_some_and(respawn::blinked_out) {
+            continue;
+        }
+        //Animated sprites have their frames side by side
+        let src = Rect::new((renderable.frame * renderable.i_w) as i32,0,renderable.i_w,renderable.i_h);
+        let (draw_x, draw_y) = game::interpolate_position(pos, prev, alpha);
+        let x = draw_x as i32;
+        let y = draw_y as i32;
         let dest = Rect::new(x - ((renderable.o_w/2) as i32), y - ((renderable.o_h/2) as i32),renderable.o_w,renderable.o_h);
 
+        //Anything that wraps is also drawn poking out of the opposite edges
+        let mut copies = vec![dest];
+        if wraps || players.contains(entity) {
+            copies.extend(wrap_ghosts(dest));
+        }
+
         let center = Point::new((renderable.o_w/2) as i32,(renderable.o_h/2) as i32);
         let texture = texture_manager.load(&renderable.tex_name)?;
-        canvas.copy_ex(
-            &texture, //Texture Object
-            src, //Source Rectangle
-            dest, //Destination Rectangle
-            renderable.rot, //Rotation
-            center, //Rotation Center
-            false, //Flip Horizontal
-            false //Flip Vertical
-        )?;
+        for copy in copies {
+            canvas.copy_ex(
+                &texture, //Texture Object
+                src, //Source Rectangle
+                copy, //Destination Rectangle
+                pos.rot, //Rotation
+                center, //Rotation Center
+                false, //Flip Horizontal
+                false //Flip Vertical
+            )?;
+        }
     }
 
-    let players = ecs.read_storage::<components::Player>();
-    for(renderable, pos, player) in (&renderables, &positions, &players).join(){
+    for player in (&players).join(){
 
         //Show Lives
         let lives: String = "Lives: ".to_string() + &player.lives.to_string();
+        //Flashes red for a moment after an extra life
+        let lives_color = if extra_life::flashed_on(player) { Color::RGBA(220,0,0,255) } else { Color::RGBA(0,0,0,255) };
         let surface = font
             .render(&lives)
-            .blended(Color::RGBA(0,0,0,255))
+            .blended(lives_color)
             .map_err(|e| e.to_string())?;
         let texture = texture_creator
             .create_texture_from_surface(&surface)
@@ -138,52 +175,14 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
 
         let target = Rect::new((SCREEN_WIDTH - 135) as i32,10 as i32,125 as u32,50 as u32);
         canvas.copy(&texture, None, Some(target));
-
-        let src = Rect::new(0,0,renderable.i_w, renderable.i_h);
-        let x: i32 = pos.x as i32;
-        let y: i32 = pos.y as i32;
-        let mut dest = Rect::new(x - ((renderable.o_w/2) as i32), y - ((renderable.o_h/2) as i32),renderable.o_w,renderable.o_h);
-
-        let mut draw_second = false;
-        if dest.x < (renderable.o_w/2).try_into().unwrap() {
-            dest.x += crate::SCREEN_WIDTH as i32;
-            draw_second = true;
-        } else if dest.x > (crate::SCREEN_WIDTH - renderable.o_w/2) as i32 {
-            dest.x -= crate::SCREEN_WIDTH as i32;
-            draw_second = true;
-        }
-
-        if dest.y < (renderable.o_h/2).try_into().unwrap() {
-            dest.y += crate::SCREEN_HEIGHT as i32;
-            draw_second = true;
-        } else if dest.y > (crate::SCREEN_HEIGHT - renderable.o_h/2) as i32 {
-            dest.y -= crate::SCREEN_HEIGHT as i32;
-            draw_second = true;
-        }
-
-        if !draw_second {
-            break;
-        }
-
-        let center = Point::new((renderable.o_w/2) as i32,(renderable.o_h/2) as i32);
-        let texture = texture_manager.load(&renderable.tex_name)?;
-        canvas.copy_ex(
-            &texture, //Texture Object
-            src, //Source Rectangle
-            dest, //Destination Rectangle
-            renderable.rot, //Rotation
-            center, //Rotation Center
-            false, //Flip Horizontal
-            false //Flip Vertical
-        )?;
-
     }
 
 
+    let best_score = ecs.read_resource::<BestScore>().0;
     let gamedatas = ecs.read_storage::<components::GameData>();
     for gamedata in (gamedatas).join(){
 
-        if &GAMESTATE.lock().unwrap().highscore > &gamedata.score {
+        if best_score > gamedata.score {
             //Show Score
             let score: String = "Score: ".to_string() + &gamedata.score.to_string();
             let surface = font
@@ -199,7 +198,7 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
             canvas.copy(&texture, None, Some(target));
 
             //Show Highscore
-            let highscore: String = "High Score: ".to_string() + &GAMESTATE.lock().unwrap().highscore.to_string();
+            let highscore: String = "High Score: ".to_string() + &best_score.to_string();
             let surface = font
                 .render(&highscore)
                 .blended(Color::RGBA(0,0,0,255))
@@ -214,7 +213,7 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
         }
         else{
             //Show Score (High)
-            let highscore: String = "Score: ".to_string() + &GAMESTATE.lock().unwrap().highscore.to_string() + " High!";
+            let highscore: String = "Score: ".to_string() + &best_score.to_string() + " High!";
             let surface = font
                 .render(&highscore)
                 .blended(Color::RGBA(0,0,0,255))
@@ -241,11 +240,41 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
         let target = Rect::new(10 as i32,(SCREEN_HEIGHT - 60 ) as i32,150 as u32,50 as u32);
         canvas.copy(&texture, None, Some(target));
 
-        if gamedata.showControls {
+        //Show the time left on a timed wave
+        if let Some(time_left) = wave::time_left(ecs) {
+            let time_text = format!("Time: {}", time_left.ceil() as u32);
+            let surface = font
+                .render(&time_text)
+                .blended(if time_left < 10.0 { Color::RGBA(255,0,0,255) } else { Color::RGBA(0,0,0,255) })
+                .map_err(|e| e.to_string())?;
+            let texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+
+            let target = Rect::new(170,(SCREEN_HEIGHT - 60 ) as i32,130,50);
+            canvas.copy(&texture, None, Some(target))?;
+        }
+
+        if gamedata.show_controls {
             //Show Controls
-            let moveControls: String = "WASD Move".to_string();
+            let hyperspace_controls: String = bindings.key_for(InputAction::Hyperspace) + " Hyperspace";
+            let surface = font
+                .render(&hyperspace_controls)
+                .blended(Color::RGBA(0,0,0,255))
+                .map_err(|e| e.to_string())?;
+            let texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())?;
+
+            let target = Rect::new((SCREEN_WIDTH - 235) as i32,(SCREEN_HEIGHT - 285 ) as i32,225,35);
+            canvas.copy(&texture, None, Some(target))?;
+
+            let move_controls: String = format!("{}/{}/{} Move",
+                bindings.key_for(InputAction::Thrust),
+                bindings.key_for(InputAction::RotateLeft),
+                bindings.key_for(InputAction::RotateRight));
             let surface = font
-                .render(&moveControls)
+                .render(&move_controls)
                 .blended(Color::RGBA(0,0,0,255))
                 .map_err(|e| e.to_string())?;
             let texture = texture_creator
@@ -255,9 +284,9 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
             let target = Rect::new((SCREEN_WIDTH - 185) as i32,(SCREEN_HEIGHT - 245 ) as i32,175 as u32,35 as u32);
             canvas.copy(&texture, None, Some(target));
 
-            let spaceControls: String = "Space Shoot".to_string();
+            let space_controls: String = bindings.key_for(InputAction::Fire) + " Shoot";
             let surface = font
-                .render(&spaceControls)
+                .render(&space_controls)
                 .blended(Color::RGBA(0,0,0,255))
                 .map_err(|e| e.to_string())?;
             let texture = texture_creator
@@ -267,9 +296,9 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
             let target = Rect::new((SCREEN_WIDTH - 210) as i32,(SCREEN_HEIGHT - 205 ) as i32,200 as u32,35 as u32);
             canvas.copy(&texture, None, Some(target));
 
-            let MusicControls: String = "P Un/Pause Music".to_string();
+            let music_controls: String = bindings.key_for(InputAction::ToggleMusic) + " Un/Pause Music";
             let surface = font
-                .render(&MusicControls)
+                .render(&music_controls)
                 .blended(Color::RGBA(0,0,0,255))
                 .map_err(|e| e.to_string())?;
             let texture = texture_creator
@@ -280,9 +309,9 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
             let target = Rect::new((SCREEN_WIDTH - 260) as i32,(SCREEN_HEIGHT - 165 ) as i32,250 as u32,35 as u32);
             canvas.copy(&texture, None, Some(target));
 
-            let InvincibleControls: String = "I Invincible".to_string();
+            let invincible_controls: String = bindings.key_for(InputAction::ToggleInvincibility) + " Invincible";
             let surface = font
-                .render(&InvincibleControls)
+                .render(&invincible_controls)
                 .blended(Color::RGBA(0,0,0,255))
                 .map_err(|e| e.to_string())?;
             let texture = texture_creator
@@ -293,9 +322,9 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
             canvas.copy(&texture, None, Some(target));
 
 
-            let AsteroidControls: String = "O 1000 Asteroids".to_string();
+            let asteroid_controls: String = bindings.key_for(InputAction::ThousandAsteroids) + " 1000 Asteroids";
             let surface = font
-                .render(&AsteroidControls)
+                .render(&asteroid_controls)
                 .blended(Color::RGBA(0,0,0,255))
                 .map_err(|e| e.to_string())?;
             let texture = texture_creator
@@ -305,9 +334,9 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
             let target = Rect::new((SCREEN_WIDTH - 260) as i32,(SCREEN_HEIGHT - 85 ) as i32,250 as u32,35 as u32);
             canvas.copy(&texture, None, Some(target));
 
-            let FPSControls: String = "U Unlock FPS".to_string();
+            let fps_controls: String = bindings.key_for(InputAction::ToggleFpsLock) + " Unlock FPS";
             let surface = font
-                .render(&FPSControls)
+                .render(&fps_controls)
                 .blended(Color::RGBA(0,0,0,255))
                 .map_err(|e| e.to_string())?;
             let texture = texture_creator
@@ -348,7 +377,7 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
         }
         else{
             //Show Info Control
-            let info: String = "H Show Info".to_string();
+            let info: String = bindings.key_for(InputAction::ToggleControls) + " Show Info";
             let surface = font
                 .render(&info)
                 .blended(Color::RGBA(0,0,0,255))
@@ -362,24 +391,309 @@ fn render(canvas: &mut WindowCanvas, texture_manager: &mut texture_manager::Text
         }
     }
 
+    if let Some(lines) = options {
+        render_options(canvas, texture_creator, font, bindings, lines)?;
+        canvas.present();
+        return Ok(());
+    }
+
+    match phase {
+        GamePhase::Paused => {
+            draw_text_centered(canvas, texture_creator, font, "PAUSED", 400, 120)?;
+            let resume = bindings.key_for(InputAction::Pause) + " to resume";
+            draw_text_centered(canvas, texture_creator, font, &resume, 540, 45)?;
+        },
+        GamePhase::LevelTransition => {
+            let (_, level) = game::get_score_and_level(ecs);
+            draw_text_centered(canvas, texture_creator, font, &format!("LEVEL {}", level), 400, 120)?;
+        },
+        GamePhase::GameOver => {
+            render_game_over(canvas, texture_creator, font, ecs, bindings, highscore_table, initials)?;
+        },
+        _ => {}
+    }
+
     canvas.present();
     Ok(())
 }
 
-struct State{ecs: World}
+// Copies of a sprite that crosses a screen edge, moved to the opposite side
+fn wrap_ghosts(dest: Rect) -> Vec<Rect> {
+    let mut offset_x = 0;
+    if dest.left() < 0 {
+        offset_x = SCREEN_WIDTH as i32;
+    } else if dest.right() > SCREEN_WIDTH as i32 {
+        offset_x = -(SCREEN_WIDTH as i32);
+    }
+
+    let mut offset_y = 0;
+    if dest.top() < 0 {
+        offset_y = SCREEN_HEIGHT as i32;
+    } else if dest.bottom() > SCREEN_HEIGHT as i32 {
+        offset_y = -(SCREEN_HEIGHT as i32);
+    }
+
+    let mut ghosts = Vec::new();
+    if offset_x != 0 {
+        ghosts.push(Rect::new(dest.x + offset_x, dest.y, dest.width(), dest.height()));
+    }
+    if offset_y != 0 {
+        ghosts.push(Rect::new(dest.x, dest.y + offset_y, dest.width(), dest.height()));
+    }
+    if offset_x != 0 && offset_y != 0 {
+        ghosts.push(Rect::new(dest.x + offset_x, dest.y + offset_y, dest.width(), dest.height()));
+    }
+    ghosts
+}
+
+// Prints the asset problems, and exits if the game can't run with them. The list goes
+// straight to stderr as returning it from main would print it all on one quoted line.
+fn report_assets(problems: &[AssetProblem]) {
+    if let Err(e) = assets::report(problems) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+// Magenta and black squares, drawn in place of textures that are missing or won't load
+fn placeholder_texture(texture_creator: &TextureCreator<WindowContext>) -> Result<Texture<'_>, String> {
+    let mut surface = Surface::new(PLACEHOLDER_WIDTH, PLACEHOLDER_HEIGHT, PixelFormatEnum::RGBA8888)?;
+    surface.fill_rect(None, Color::BLACK)?;
+    for row in 0..PLACEHOLDER_HEIGHT / PLACEHOLDER_SQUARE {
+        for column in 0..PLACEHOLDER_WIDTH / PLACEHOLDER_SQUARE {
+            if (row + column) % 2 == 0 {
+                let square = Rect::new((column * PLACEHOLDER_SQUARE) as i32, (row * PLACEHOLDER_SQUARE) as i32, PLACEHOLDER_SQUARE, PLACEHOLDER_SQUARE);
+                surface.fill_rect(square, Color::MAGENTA)?;
+            }
+        }
+    }
+    texture_creator.create_texture_from_surface(&surface).map_err(|e| e.to_string())
+}
+
+fn render_title(canvas: &mut WindowCanvas, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, bindings: &Bindings, highscore_table: &HighScoreTable) -> Result<(),String> {
+    draw_text_centered(canvas, texture_creator, font, "ASTEROIDS", 150, 160)?;
+
+    let start = format!("Press {} or {} to start", bindings.key_for(InputAction::Pause), bindings.key_for(InputAction::Fire));
+    draw_text_centered(canvas, texture_creator, font, &start, 350, 50)?;
+    draw_text_centered(canvas, texture_creator, font, &format!("{} for sound options", bindings.key_for(InputAction::Options)), 405, 35)?;
+
+    render_high_scores(canvas, texture_creator, font, highscore_table, 460)
+}
+
+fn render_options(canvas: &mut WindowCanvas, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, bindings: &Bindings, lines: &[String]) -> Result<(),String> {
+    draw_text_centered(canvas, texture_creator, font, "OPTIONS", 150, 120)?;
+    for (index, line) in lines.iter().enumerate() {
+        draw_text_centered(canvas, texture_creator, font, line, 330 + index as i32 * 60, 45)?;
+    }
+
+    let help = format!("{}/{} choose  {}/{} change  {} close",
+        bindings.key_for(InputAction::Thrust),
+        bindings.key_for(InputAction::Hyperspace),
+        bindings.key_for(InputAction::RotateLeft),
+        bindings.key_for(InputAction::RotateRight),
+        bindings.key_for(InputAction::Options));
+    draw_text_centered(canvas, texture_creator, font, &help, 330 + lines.len() as i32 * 60 + 40, 35)
+}
+
+fn render_game_over(canvas: &mut WindowCanvas, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, ecs: &World, bindings: &Bindings, highscore_table: &HighScoreTable, initials: Option<&str>) -> Result<(),String> {
+    let (score, level) = game::get_score_and_level(ecs);
+
+    draw_text_centered(canvas, texture_creator, font, "GAME OVER", 150, 120)?;
+    draw_text_centered(canvas, texture_creator, font, &format!("Score: {}  Level: {}", score, level), 290, 50)?;
+
+    match initials {
+        Some(initials) => {
+            let prompt = format!("New high score! Enter initials: {}_", initials);
+            draw_text_centered(canvas, texture_creator, font, &prompt, 370, 45)?;
+        },
+        None => {
+            let again = format!("Press {} to play again", bindings.key_for(InputAction::Pause));
+            draw_text_centered(canvas, texture_creator, font, &again, 370, 45)?;
+        }
+    }
+
+    render_high_scores(canvas, texture_creator, font, highscore_table, 460)
+}
+
+fn render_high_scores(canvas: &mut WindowCanvas, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, highscore_table: &HighScoreTable, y: i32) -> Result<(),String> {
+    draw_text_centered(canvas, texture_creator, font, "HIGH SCORES", y, 50)?;
+    for (rank, entry) in highscore_table.entries.iter().enumerate() {
+        let line = format!("{:>2}. {:<3} {:>7}  Level {:<3} {}", rank + 1, entry.initials, entry.score, entry.level, entry.date);
+        draw_text_centered(canvas, texture_creator, font, &line, y + 70 + (rank as i32) * 45, 38)?;
+    }
+
+    Ok(())
+}
+
+// Draws a line of text centred horizontally, scaled to the given height
+fn draw_text_centered(canvas: &mut WindowCanvas, texture_creator: &TextureCreator<WindowContext>, font: &sdl2::ttf::Font, text: &str, y: i32, height: u32) -> Result<(),String> {
+    let surface = font
+        .render(text)
+        .blended(Color::RGBA(0,0,0,255))
+        .map_err(|e| e.to_string())?;
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+
+    let width = surface.width() * height / surface.height();
+    let target = Rect::new((SCREEN_WIDTH as i32 - width as i32) / 2, y, width, height);
+    canvas.copy(&texture, None, Some(target))
+}
+
+fn save_high_score(highscore_table: &mut HighScoreTable, path: &Path, initials: String, gs: &Simulation) {
+    let (score, level) = game::get_score_and_level(&gs.ecs);
+    let initials = if initials.is_empty() { "???".to_string() } else { initials };
+    highscore_table.insert(HighScore{
+        initials,
+        score,
+        level,
+        date: highscores::today(),
+        seed: gs.seed()
+    });
+
+    if let Err(e) = highscore_table.save(path) {
+        println!("Could not save high scores: {}", e);
+    }
+}
 
-pub struct GameState{
-    highscore: u32
+// Game mode given with --mode, bounce if absent
+fn parse_mode(args: &[String]) -> Result<GameMode,String> {
+    match flag_value(args, "--mode")? {
+        Some(value) => GameMode::parse(value),
+        None => Ok(GameMode::default())
+    }
 }
 
-static GAMESTATE: Lazy<Mutex<GameState>> = Lazy::new(|| {
-    Mutex::new(GameState{
-        highscore: 0
+// Chance in percent of exploding when leaving hyperspace, given with --hyperspace-risk
+fn parse_hyperspace_risk(args: &[String]) -> Result<u8,String> {
+    match flag_value(args, "--hyperspace-risk")? {
+        Some(value) => match value.parse::<u8>() {
+            Ok(percent) if percent <= 100 => Ok(percent),
+            _ => Err(format!("Invalid --hyperspace-risk '{}', expected a percentage from 0 to 100", value))
+        },
+        None => Ok(HyperspaceRisk::default().0)
+    }
+}
+
+// Points between extra lives, given with --extra-life (0 turns them off)
+fn parse_extra_life_score(args: &[String]) -> Result<u32,String> {
+    match flag_value(args, "--extra-life")? {
+        Some(value) => value.parse::<u32>().map_err(|e| format!("Invalid --extra-life: {}", e)),
+        None => Ok(ExtraLifeScore::default().0)
+    }
+}
+
+fn parse_settings(args: &[String]) -> Result<GameSettings,String> {
+    Ok(GameSettings {
+        asteroid_collisions: args.iter().any(|arg| arg == "--asteroid-collisions"),
+        hyperspace_risk: parse_hyperspace_risk(args)?,
+        extra_life_score: parse_extra_life_score(args)?
     })
-});
+}
+
+// Uses the seed given with --seed, or picks a random one so it can be reported
+fn parse_seed(args: &[String]) -> Result<u64,String> {
+    match flag_value(args, "--seed")? {
+        Some(value) => value.parse::<u64>().map_err(|e| format!("Invalid --seed: {}", e)),
+        None => Ok(rand::random::<u64>())
+    }
+}
+
+// Value following a flag such as "--replay <file>", if the flag was given
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a String>,String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(i) => args.get(i + 1).map(Some).ok_or(format!("{} needs a value", flag)),
+        None => Ok(None)
+    }
+}
+
+// Plays back the replay given with --replay, or starts a new simulation from the command line settings
+fn build_simulation(args: &[String], seed: u64, mode: GameMode) -> Result<Simulation<'static, 'static>,String> {
+    let tiers = AsteroidTiers::load(ASTEROIDS_FILENAME)?;
+    let waves = Waves::load(WAVES_FILENAME, &tiers)?;
+    match flag_value(args, "--replay")? {
+        Some(path) => {
+            println!("Playing replay {}", path);
+            Simulation::from_replay(Replay::load(path)?, tiers, waves)
+        },
+        None => {
+            let mut sim = Simulation::new(seed, mode);
+            sim.apply_settings(parse_settings(args)?);
+            sim.set_asteroid_tiers(tiers);
+            sim.set_waves(waves);
+            Ok(sim)
+        }
+    }
+}
+
+// Runs the simulation without a window or audio device and prints the final state
+fn run_headless(args: &[String], seed: u64, mode: GameMode) -> Result<(),String> {
+    let sim = build_simulation(args, seed, mode)?;
+    //A replay brings its own seed, so this is only known once it has loaded
+    println!("Seed: {} Mode: {}", sim.seed(), sim.mode().name());
+    if sim.is_playing_replay() {
+        let report = headless::run_replay(sim);
+        print_report(&report);
+        return Ok(());
+    }
+
+    let mut ticks = headless::DEFAULT_TICKS;
+    let mut script = Vec::new();
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--ticks" => {
+                i += 1;
+                let value = args.get(i).ok_or("--ticks needs a value")?;
+                ticks = value.parse::<u64>().map_err(|e| format!("Invalid --ticks: {}", e))?;
+            },
+            "--script" => {
+                i += 1;
+                let path = args.get(i).ok_or("--script needs a file")?;
+                script = headless::load_script(path)?;
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let report = headless::run(sim, ticks, headless::DEFAULT_DELTA_TIME, &script);
+    print_report(&report);
+    Ok(())
+}
+
+fn print_report(report: &headless::HeadlessReport) {
+    println!("Seed: {} Ticks: {} Score: {} Level: {} Lives: {}{}", report.seed, report.ticks, report.score, report.level, report.lives,
+        if report.game_over { " (Game Over)" } else { "" });
+}
 
 fn main() -> Result<(),String>{
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let seed = parse_seed(&args)?;
+    let mode = parse_mode(&args)?;
+
+    if args.iter().any(|arg| arg == "--headless") {
+        println!("Starting Asteroids (headless)!");
+        return run_headless(&args, seed, mode);
+    }
+
     println!("Starting Asteroids!");
+
+    let record_path = flag_value(&args, "--record")?;
+    let mut gs = build_simulation(&args, seed, mode)?;
+    if record_path.is_some() {
+        gs.start_recording();
+    }
+    println!("Seed: {} Mode: {}", gs.seed(), gs.mode().name());
+
+    //Look over every file the game needs before opening the window, so a missing font
+    //stops it straight away with the whole list of what is wrong
+    let asset_manifest = assets::manifest(&gs.asteroid_tiers());
+    let mut asset_problems = assets::check(&asset_manifest);
+    if asset_problems.iter().any(|problem| problem.is_fatal()) {
+        report_assets(&asset_problems);
+    }
     
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
@@ -396,54 +710,86 @@ fn main() -> Result<(),String>{
     let texture_creator = canvas.texture_creator();
     let mut texture_manager = texture_manager::TextureManager::new(&texture_creator);
 
-    //Load Images
-    texture_manager.load("img/ship.png")?; //Loads Ship Texture to Memory
-    texture_manager.load("img/asteroid1.png")?; //Loads Asteroid Texture to Memory
-    texture_manager.load("img/missile.png")?; //Loads Missile Texture to Memory
+    //Load Images (Ship, Asteroids, Missile, Saucer and the Hyperspace and Explosion Effect) to Memory.
+    //Anything missing or broken is drawn as a placeholder instead.
+    let placeholder = Rc::new(placeholder_texture(&texture_creator)?);
+    for asset in &asset_manifest {
+        if asset.kind != AssetKind::Texture {
+            continue;
+        }
+        if !assets::has_problem(&asset_problems, &asset.path) {
+            match texture_manager.load(asset.path.as_str()) {
+                Ok(_) => continue,
+                Err(e) => asset_problems.push(AssetProblem::new(&asset.path, AssetKind::Texture, e))
+            }
+        }
+        texture_manager.insert(asset.path.clone(), placeholder.clone());
+    }
 
 
     //Sound Manager
-    let mut sound_manager = sound_manager::SoundManager::new();
+    let mut sound_manager = SoundManager::new(sound_manager::default_backend());
 
-    //Load the soudns to prevent loading during gameplay
-    sound_manager.load_sound(&MUSIC_FILENAME.to_string(), true);
-    sound_manager.load_sound(&THRUSTER_FILENAME.to_string(), true);
-    sound_manager.load_sound(&SHOOT_FILENAME.to_string(), false);
-    sound_manager.load_sound(&RELOAD_FILENAME.to_string(), false);
+    //Load the soudns to prevent loading during gameplay. The ones that won't load stay silent.
+    for sound in Sound::ALL {
+        if let Err(e) = sound_manager.load_sound(sound.filename(), sound.looped(), sound.bus()) {
+            if !assets::has_problem(&asset_problems, sound.filename()) {
+                asset_problems.push(AssetProblem::new(sound.filename(), AssetKind::Sound, e));
+            }
+        }
+    }
+
+    //Mixer levels from the options menu, kept in the user data directory
+    let settings_path = settings::default_path();
+    let mut settings = Settings::load(&settings_path).unwrap_or_else(|e| {
+        println!("Could not load settings: {}", e);
+        Settings::default()
+    });
+    sound_manager.set_mixer(settings.audio);
+    let mut options = options_menu::OptionsMenu::new();
 
     //Prepare fonts
     let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
-    let font_path: &Path = Path::new(&"fonts/Monocraft.ttf");
-    let mut font = ttf_context.load_font(font_path, 128)?;
+    let font_path: &Path = Path::new(assets::FONT_FILENAME);
+    let mut font = match ttf_context.load_font(font_path, 128) {
+        Ok(font) => font,
+        Err(e) => {
+            //Text can't be drawn without it, so this always ends the game
+            asset_problems.push(AssetProblem::new(assets::FONT_FILENAME, AssetKind::Font, e));
+            report_assets(&asset_problems);
+            std::process::exit(1);
+        }
+    };
     font.set_style(sdl2::ttf::FontStyle::BOLD);
+
+    //Everything that had to be swapped for a placeholder or silence, in one list
+    report_assets(&asset_problems);
     
+    //Load the high score table from the user data directory
+    let highscore_path = highscores::default_path();
+    let mut highscore_table = HighScoreTable::load(&highscore_path).unwrap_or_else(|e| {
+        println!("Could not load high scores: {}", e);
+        HighScoreTable::default()
+    });
+    gs.set_best_score(highscore_table.best());
+
+    //Load key bindings, falling back to the defaults when there is no bindings file
+    let bindings = Bindings::load(BINDINGS_FILENAME)?;
+    for key_name in bindings.key_names() {
+        if Keycode::from_name(key_name).is_none() {
+            println!("Unknown key \"{}\" in {}", key_name, BINDINGS_FILENAME);
+        }
+    }
+
+    //Game controllers are opened as SDL reports them, including ones plugged in later
+    let mut gamepads = gamepad::Gamepads::new(sdl_context.game_controller()?);
+
     let mut event_pump = sdl_context.event_pump()?;
-    let mut key_manager: HashMap<String,bool> = HashMap::new();
+    let mut key_manager = ActionState::new();
 
-    let mut gs = State{
-        ecs: World::new()
-    };
-    gs.ecs.register::<components::Position>();
-    gs.ecs.register::<components::Renderable>();
-    gs.ecs.register::<components::Player>();
-    gs.ecs.register::<components::Asteroid>();
-    gs.ecs.register::<components::Missile>();
-    gs.ecs.register::<components::GameData>();
-    // gs.ecs.register::<components::Star>();
-    gs.ecs.register::<components::SoundCue>();
-    gs.ecs.insert(DeltaTime(0.0));
-
-    let mut dispatcher = DispatcherBuilder::new() //Creates a dispatcher to run systems
-        .with(asteroid::AsteroidMover, "asteroid_mover", &[])
-        .with(asteroid::AsteroidCollider, "asteroid_collider", &[])
-        .with(missile::MissileMover, "missile_mover", &[])
-        .with(missile::MissileStriker, "missile_striker", &[])
-        .build();
-
-    game::load_world(&mut gs.ecs);
 
     //Start Music Playing
-    sound_manager.resume_sound(&MUSIC_FILENAME.to_string());
+    sound_manager.handle(SoundEvent::Loop(Sound::Music));
 
     let mut frame_count = 0u64;
     let mut last_frame_time = Instant::now();
@@ -456,62 +802,160 @@ fn main() -> Result<(),String>{
     let mut unlockedFPS = false;
     let mut musicPlaying = true;
 
+    //Initials typed on the game over screen, while a new high score is being entered
+    let mut initials: Option<String> = None;
+    let mut game_over_since: Option<Instant> = None;
+
     'running: loop {
         for event in event_pump.poll_iter(){
-            match event {
+            let inputs: Vec<(InputAction, bool)> = match event {
                 Event::Quit {..} => {
                     break 'running
-                }, 
-                Event::KeyDown {keycode: Some(Keycode::Escape),..} => {
-                    break 'running
-                },
-                Event::KeyDown {keycode: Some(Keycode::Space),..} => {
-                    utils::key_down(&mut key_manager, " ".to_string());
-                },
-                Event::KeyUp {keycode: Some(Keycode::Space),..} => {
-                    utils::key_up(&mut key_manager, " ".to_string());
                 },
-                Event::KeyUp {keycode: Some(Keycode::P),..} => {
-                    musicPlaying = !musicPlaying;
-                    if musicPlaying {
-                        sound_manager.resume_sound(&MUSIC_FILENAME.to_string())
-                    } else {
-                        sound_manager.stop_sound(&MUSIC_FILENAME.to_string());
-                    }
-                },
-                Event::KeyUp {keycode: Some(Keycode::U),..} => {
-                    println!("FPS Toggle");
-                    unlockedFPS = !unlockedFPS;
-                    if unlockedFPS {
-                        sound_manager.stop_sound(&THRUSTER_FILENAME.to_string());
+                Event::KeyDown {keycode: Some(key),..} if initials.is_some() => {
+                    match key {
+                        Keycode::Return => {
+                            let typed = initials.take().unwrap_or_default();
+                            save_high_score(&mut highscore_table, &highscore_path, typed, &gs);
+                        },
+                        Keycode::Escape => {
+                            //Skips the initials, the score still goes in the table
+                            initials = None;
+                            save_high_score(&mut highscore_table, &highscore_path, String::new(), &gs);
+                        },
+                        Keycode::Backspace => {
+                            if let Some(typed) = initials.as_mut() {
+                                typed.pop();
+                            }
+                        },
+                        _ => {
+                            let name = key.name();
+                            if let Some(typed) = initials.as_mut() {
+                                if typed.len() < 3 && name.len() == 1 && name.chars().all(|c| c.is_ascii_alphanumeric()) {
+                                    typed.push_str(&name);
+                                }
+                            }
+                        }
                     }
+                    Vec::new()
                 },
-                Event::KeyUp {keycode:Some(Keycode::I),..} => {
-                    game::toggle_invincibility(&mut gs.ecs);
+                Event::KeyUp {..} if initials.is_some() => {
+                    Vec::new()
                 },
-                Event::KeyUp {keycode:Some(Keycode::O),..} => {
-                    game::create_thousand_asteroids(&mut gs.ecs);
+                Event::KeyDown {keycode: Some(key), repeat,..} => {
+                    //Key repeat only matters for held actions; it would flicker the toggles
+                    bindings.action_for(&key.name())
+                        .filter(|action| !repeat || action.is_held())
+                        .map(|action| (action, true)).into_iter().collect()
                 },
-                Event::KeyUp {keycode:Some(Keycode::H),..} => {
-                    game::toggle_show_controls(&mut gs.ecs);
+                Event::KeyUp {keycode: Some(key),..} => {
+                    bindings.action_for(&key.name()).map(|action| (action, false)).into_iter().collect()
                 },
-                Event::KeyDown {keycode,..} => {
-                    match keycode {
-                        None => {},
-                        Some(key) => {
-                            utils::key_down(&mut key_manager, key.to_string());
-                        }
+                _ => {
+                    let inputs = gamepads.handle_event(&event);
+                    key_manager.set_rotate_amount(gamepads.rotate_amount());
+                    inputs
+                }
+            };
+
+            for (action, pressed) in inputs {
+                if options.open {
+                    //Held keys still have to be let go of, everything else goes to the menu
+                    if !pressed && action.is_held() {
+                        utils::key_up(&mut key_manager, action);
                     }
-                },
-                Event::KeyUp {keycode,..} => {
-                    match keycode {
-                        None => {},
-                        Some(key) => {
-                            utils::key_up(&mut key_manager, key.to_string());
-                        }
+                    if !pressed {
+                        continue;
                     }
-                },
-                _ => {}
+                    match options.handle(action, &mut settings.audio) {
+                        MenuResult::Moved => {
+                            sound_manager.handle(SoundEvent::Play(Sound::Menu));
+                        },
+                        MenuResult::Changed => {
+                            sound_manager.set_mixer(settings.audio);
+                            sound_manager.handle(SoundEvent::Play(Sound::Menu));
+                            if let Err(e) = settings.save(&settings_path) {
+                                println!("Could not save settings: {}", e);
+                            }
+                        },
+                        MenuResult::Closed => {
+                            if options.paused_game {
+                                gs.toggle_pause();
+                            }
+                        },
+                        MenuResult::Nothing => {}
+                    }
+                    continue;
+                }
+
+                match (action, pressed) {
+                    (InputAction::Quit, true) => {
+                        break 'running
+                    },
+                    //A controller can't type, so Start or A puts the score in with whatever has been typed
+                    (InputAction::Pause | InputAction::Fire, true) if initials.is_some() => {
+                        //Not straight away, so a button still held from playing doesn't skip the entry
+                        let ready = game_over_since.is_some_and(|since| since.elapsed() > GAME_OVER_MIN_DURATION);
+                        if ready {
+                            let typed = initials.take().unwrap_or_default();
+                            save_high_score(&mut highscore_table, &highscore_path, typed, &gs);
+                        }
+                    },
+                    (InputAction::Pause, true) => {
+                        match gs.phase() {
+                            GamePhase::Title | GamePhase::GameOver => gs.start_game(),
+                            _ => gs.toggle_pause()
+                        }
+                    },
+                    //Fire also starts a game, but the game over screen has to be up long enough not to be skipped by accident
+                    (InputAction::Fire, true) if gs.phase() == GamePhase::Title
+                        || (initials.is_none() && game_over_since.is_some_and(|since| since.elapsed() > GAME_OVER_MIN_DURATION)) => {
+                        gs.start_game();
+                    },
+                    (action, true) if action.is_held() => {
+                        utils::key_down(&mut key_manager, action);
+                    },
+                    (action, false) if action.is_held() => {
+                        utils::key_up(&mut key_manager, action);
+                    },
+                    (InputAction::ToggleMusic, false) => {
+                        musicPlaying = !musicPlaying;
+                        if musicPlaying {
+                            sound_manager.handle(SoundEvent::Loop(Sound::Music));
+                        } else {
+                            sound_manager.handle(SoundEvent::Stop(Sound::Music));
+                        }
+                    },
+                    (InputAction::ToggleFpsLock, false) => {
+                        println!("FPS Toggle");
+                        unlockedFPS = !unlockedFPS;
+                        if unlockedFPS {
+                            //Sounds are skipped while unlocked, so nothing would stop the loops
+                            for sound in [Sound::Thruster, Sound::SaucerLarge, Sound::SaucerSmall] {
+                                sound_manager.handle(SoundEvent::Stop(sound));
+                            }
+                        }
+                    },
+                    (InputAction::ToggleInvincibility, false) => {
+                        gs.toggle_invincibility();
+                    },
+                    (InputAction::ThousandAsteroids, false) => {
+                        gs.create_thousand_asteroids();
+                    },
+                    (InputAction::ToggleControls, false) => {
+                        game::toggle_show_controls(&mut gs.ecs);
+                    },
+                    (InputAction::Options, true) => {
+                        //The game stops while the menu is up
+                        options.open = true;
+                        options.paused_game = matches!(gs.phase(), GamePhase::Playing | GamePhase::LevelTransition);
+                        if options.paused_game {
+                            gs.toggle_pause();
+                        }
+                        sound_manager.handle(SoundEvent::Play(Sound::Menu));
+                    },
+                    _ => {}
+                }
             }
         }
 
@@ -531,32 +975,48 @@ fn main() -> Result<(),String>{
         //     last_frame_time_fps += Duration::new(1, 0);
         // }
 
-        gs.ecs.write_resource::<DeltaTime>().0 = delta_time;
-
-        game::update(&mut gs.ecs, &mut key_manager,delta_time);
-        dispatcher.dispatch(&mut gs.ecs); //Runs the dispatcher and all systems run events
-        gs.ecs.maintain(); //Removes all entities that have been deleted
-
-        let _ = render(&mut canvas,&mut texture_manager, &texture_creator,&font, &gs.ecs, fps);
-        let cues = gs.ecs.read_storage::<components::SoundCue>();
-        let entities = gs.ecs.entities();
-        for (cue, entitiy) in (&cues, &entities).join() {
-            if(!unlockedFPS) {
-                if cue.sc_type == components::SoundCueType::PlaySound {
-                    sound_manager.play_sound(cue.filename.to_string());
-                } else if cue.sc_type == components::SoundCueType::LoopSound {
-                    sound_manager.resume_sound(&cue.filename.to_string());
-                } else if cue.sc_type == components::SoundCueType::StopSound {
-                    sound_manager.stop_sound(&cue.filename.to_string());
+        let alpha = gs.advance(&mut key_manager, delta_time);
+        sound_manager.update(delta_time);
+
+        match (gs.phase() == GamePhase::GameOver, game_over_since) {
+            (true, None) => {
+                game_over_since = Some(Instant::now());
+                let (score, _) = game::get_score_and_level(&gs.ecs);
+                if !gs.is_playing_replay() && highscore_table.qualifies(score) {
+                    initials = Some(String::new());
                 }
+            },
+            (false, Some(_)) => {
+                game_over_since = None;
+                initials = None;
+            },
+            _ => {}
+        }
+
+        let options_lines = options.open.then(|| options.lines(&settings.audio));
+        let overlay = Overlay{bindings: &bindings, highscore_table: &highscore_table, initials: initials.as_deref(), options: options_lines.as_deref(), fps};
+        let _ = render(&mut canvas,&mut texture_manager, &texture_creator,&font, &gs.ecs, overlay, alpha);
+        let sound_events = gs.ecs.write_resource::<SoundEvents>().drain();
+        if !unlockedFPS {
+            for event in sound_events {
+                sound_manager.handle(event);
             }
-            entities.delete(entitiy).ok();
         }
+        if gs.replay_finished() {
+            println!("Replay finished at tick {}", gs.tick());
+            break 'running;
+        }
+
         if(!unlockedFPS){
             std::thread::sleep(Duration::new(0,1_000_000_000u32/60));
         }
     }
 
+    if let (Some(path), Some(recording)) = (record_path, gs.take_recording()) {
+        recording.save(path)?;
+        println!("Saved replay to {}", path);
+    }
+
     Ok(())
 }
 
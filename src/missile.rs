@@ -1,54 +1,56 @@
 use specs::prelude::*;
-use specs::{World,WorldExt,Entities,Join};
+use specs::{World,Entities,Join};
 
-use crate::sound_manager;
+use crate::{asteroid, components, collision, effect, game, AsteroidCollisions, ExtraLifeScore, GameMode, GameRng};
+use crate::asteroid::AsteroidTiers;
+use crate::collision::{CollisionEvent, CollisionEvents};
+use crate::sound::{Sound, SoundEvents};
 
-use crate::{components};
+const BIG_EXPLOSION_SIZE: u32 = 60; //Asteroids drawn at least this big go off with the deep explosion
 
-pub struct MissileMover;
+fn explosion_sound(tier: &asteroid::AsteroidTier) -> Sound {
+    if tier.size >= BIG_EXPLOSION_SIZE { Sound::ExplosionBig } else { Sound::ExplosionSmall }
+}
+
+// Removes missiles that burnt out or left the screen
+pub struct MissileExpiry;
 
-impl<'a> System<'a> for MissileMover {
+impl<'a> System<'a> for MissileExpiry {
     type SystemData = (
-        WriteStorage<'a, components::Position>,
-        WriteStorage<'a, components::Renderable>,
+        ReadStorage<'a, components::Position>,
         WriteStorage<'a, components::Missile>,
+        Write<'a, SoundEvents>,
         Entities<'a>,
-        Read<'a,crate::DeltaTime>
+        Read<'a,crate::DeltaTime>,
+        Read<'a,GameMode>
     );
 
-    fn run(&mut self, mut data: Self::SystemData) {
-        let (mut positions, mut renderables, missiles, entities, deltatime) = data;
-        for(pos, rend, missile, entity) in (&mut positions, &mut renderables, &missiles, &entities).join(){
-            let radians = pos.rot.to_radians();
-
-            let move_x = missile.speed * radians.sin() * deltatime.0;
-            let move_y = missile.speed * radians.cos() * deltatime.0;
-            pos.x += move_x;
-            pos.y -= move_y;
-
-            pos.section = (pos.x as u32/ crate::SECTION_WIDTH) * crate::NO_OF_SECTIONS +  (pos.y as u32 / crate::SECTION_HEIGHT);
-
-            if pos.x > crate::SCREEN_WIDTH.into() || pos.x < 0.0 || pos.y > crate::SCREEN_HEIGHT.into() || pos.y < 0.0 {
+    fn run(&mut self, data: Self::SystemData) {
+        let (positions, mut missiles, mut sounds, entities, deltatime, mode) = data;
+        for(pos, missile, entity) in (&positions, &mut missiles, &entities).join(){
+            //Missiles wrap around with the screen until they burn out, otherwise they are gone once off screen
+            let expired = if mode.wraps() {
+                missile.lifetime -= deltatime.0;
+                missile.lifetime <= 0.0
+            } else {
+                pos.x > crate::SCREEN_WIDTH.into() || pos.x < 0.0 || pos.y > crate::SCREEN_HEIGHT.into() || pos.y < 0.0
+            };
+
+            if expired {
                 entities.delete(entity).unwrap();
 
-                let mut sound_manager = sound_manager::SoundManager::new();
-                sound_manager.load_sound(&crate::RELOAD_FILENAME.to_string(), false);
-                sound_manager.play_sound(crate::RELOAD_FILENAME.to_string());
-                // let mut ecs = World::default();
-                // ecs.create_entity()
-                //     .with(components::SoundCue{
-                //         filename: crate::SHOOT_FILENAME.to_string(),
-                //         sc_type: components::SoundCueType::PlaySound
-                //     })
-                //     .build();
+                sounds.play_at(Sound::Reload, pos.x);
             }
-
-            rend.rot = pos.rot;
         }
     }
 }
 
-pub struct MissileStriker;
+// Damages asteroids hit by a missile. Once one runs out of hit points it scores,
+// breaks up according to its tier and, if it is explosive, takes its neighbours with it.
+#[derive(Default)]
+pub struct MissileStriker {
+    reader: Option<ReaderId<CollisionEvent>>
+}
 
 impl<'a> System<'a> for MissileStriker {
     type SystemData = (
@@ -57,79 +59,139 @@ impl<'a> System<'a> for MissileStriker {
         WriteStorage<'a, components::Missile>,
         WriteStorage<'a, components::Asteroid>,
         WriteStorage<'a, components::GameData>,
-        Entities<'a>
+        Write<'a, SoundEvents>,
+        Entities<'a>,
+        Read<'a,CollisionEvents>,
+        WriteStorage<'a, components::Collider>,
+        Read<'a,AsteroidCollisions>,
+        WriteStorage<'a, components::Velocity>,
+        WriteStorage<'a, components::AngularVelocity>,
+        Read<'a,ExtraLifeScore>,
+        ReadExpect<'a,AsteroidTiers>,
+        WriteExpect<'a,GameRng>,
+        WriteStorage<'a, components::Player>,
+        WriteStorage<'a, components::Effect>,
+        Read<'a,GameMode>,
+        Write<'a,crate::BestScore>
     );
 
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(collision::register_reader(world));
+    }
+
     fn run(&mut self, data: Self::SystemData) {
-        let (positions, renderables, missiles, asteroids,_, entities) = &data;
+        let (mut positions, mut renderables, missiles, mut asteroids, mut game_data, mut sounds, entities, events, mut colliders, collisions, mut velocities, mut spins, extra_life, tiers, mut game_rng, mut players, mut effects, mode, mut best) = data;
+        let reader = self.reader.as_mut().expect("MissileStriker used without setup");
         let mut asteroid_creation = Vec::<components::PendingAsteroid>::new();
         let mut score:u32 = 0;
-
-        for( asteroid_pos,asteroid_rend,_, asteroid_entity) in (positions,renderables,asteroids,entities).join(){
-            for(missile_pos,_,_,missile_entity) in (positions,renderables,missiles,entities).join(){
-                if asteroid_pos.section != missile_pos.section {
-                    continue;
+        let mut bangs = Vec::<(Sound, f64)>::new(); //Explosion sounds and the x they went off at
+        let mut used = Vec::<Entity>::new(); //A missile only hits once
+        let mut broken = Vec::<(Entity, bool)>::new(); //Asteroids out of hit points, and whether they score
+
+        for event in events.read(reader){
+            let (missile_entity, asteroid_entity) = match event.matching(missiles.mask(), asteroids.mask()) {
+                Some(pair) => pair,
+                None => continue
+            };
+            if used.contains(&missile_entity) || broken.iter().any(|(entity, _)| *entity == asteroid_entity) {
+                continue;
+            }
+            let asteroid = match asteroids.get_mut(asteroid_entity) {
+                Some(asteroid) => asteroid,
+                None => continue
+            };
+
+            entities.delete(missile_entity).ok();
+            used.push(missile_entity);
+
+            asteroid.hit_points = asteroid.hit_points.saturating_sub(1);
+            if asteroid.hit_points > 0 {
+                //A hit that doesn't break it through just chips it
+                if let Some(pos) = positions.get(asteroid_entity) {
+                    bangs.push((Sound::ExplosionSmall, pos.x));
                 }
+            } else {
+                //Saucer missiles break asteroids up too, but only the player scores
+                let scores = !missiles.get(missile_entity).is_some_and(|missile| missile.hostile);
+                broken.push((asteroid_entity, scores));
+            }
+        }
 
-                let diff_x = (asteroid_pos.x - missile_pos.x).abs();
-                let diff_y = (asteroid_pos.y - missile_pos.y).abs();
-                let dist = (diff_x * diff_x + diff_y * diff_y);
-
-                if dist < (asteroid_rend.o_w as f64  / 2.0)*(asteroid_rend.o_w as f64  / 2.0){
-                    score += 10;
-                    entities.delete(missile_entity).ok();
-                    entities.delete(asteroid_entity).ok();
-
-                    let mut sound_manager = sound_manager::SoundManager::new();
-                    sound_manager.load_sound(&crate::RELOAD_FILENAME.to_string(), false);
-                    sound_manager.play_sound(crate::RELOAD_FILENAME.to_string());
-
-                    let new_size = asteroid_rend.o_w / 2;
-                    if new_size >= 25 {
-                        asteroid_creation.push(components::PendingAsteroid{
-                            x: asteroid_pos.x,
-                            y: asteroid_pos.y,
-                            rot: asteroid_pos.rot - 90.0,
-                            section: asteroid_pos.section,
-                            size: new_size
-                        });
-                        asteroid_creation.push(components::PendingAsteroid{
-                            x: asteroid_pos.x,
-                            y: asteroid_pos.y,
-                            rot: asteroid_pos.rot + 90.0,
-                            section: asteroid_pos.section,
-                            size: new_size
-                        });
+        //Explosions add the asteroids they catch to the end of the list, so they can set off more
+        let mut blasts = Vec::<(components::Position, f64)>::new();
+        let mut i = 0;
+        while i < broken.len() {
+            let (asteroid_entity, scores) = broken[i];
+            i += 1;
+            let (asteroid_pos, asteroid_vel, tier) = match (positions.get(asteroid_entity), velocities.get(asteroid_entity), asteroids.get(asteroid_entity)) {
+                (Some(pos), Some(vel), Some(asteroid)) => match tiers.get(asteroid.tier) {
+                    Some(tier) => (components::Position{x: pos.x, y: pos.y, rot: 0.0}, vel.heading(), tier),
+                    None => continue
+                },
+                _ => continue
+            };
+            entities.delete(asteroid_entity).ok();
+            if scores {
+                score += tier.points;
+            }
+            bangs.push((explosion_sound(tier), asteroid_pos.x));
+
+            for (split, heading) in tier.splits.iter().zip(asteroid::split_headings(asteroid_vel, tier.splits.len())) {
+                asteroid_creation.push(components::PendingAsteroid{
+                    x: asteroid_pos.x,
+                    y: asteroid_pos.y,
+                    heading,
+                    tier: *split
+                });
+            }
+
+            if tier.blast_radius > 0.0 {
+                for (pos, _, entity) in (&positions, &asteroids, &entities).join() {
+                    let (diff_x, diff_y) = game::position_delta(*mode, pos, &asteroid_pos);
+                    if diff_x * diff_x + diff_y * diff_y < tier.blast_radius * tier.blast_radius
+                        && !broken.iter().any(|(other, _)| *other == entity) {
+                        broken.push((entity, scores));
+                    }
+                }
+                for (player, pos, entity) in (&mut players, &positions, &entities).join() {
+                    let (diff_x, diff_y) = game::position_delta(*mode, pos, &asteroid_pos);
+                    if game::in_play(player) && diff_x * diff_x + diff_y * diff_y < tier.blast_radius * tier.blast_radius {
+                        game::kill_player(player, entity, &entities);
                     }
                 }
+                blasts.push((asteroid_pos, tier.blast_radius));
             }
         }
 
-        let (mut positions, mut renderables, _, mut asteroids,_,entities) = data;
-        // let (ref positions, ref renderables, _, ref asteroids,ref entities) = data;
         for new_asteroid in asteroid_creation {
+            let tier = match tiers.get(new_asteroid.tier) {
+                Some(tier) => tier,
+                None => continue
+            };
+            let (velocity, spin, renderable, asteroid, collider) = asteroid::asteroid_parts(tier, new_asteroid.tier, new_asteroid.heading, &mut game_rng.rng, *collisions);
             let new_ast = entities.create();
-            positions.insert(new_ast, components::Position{x:new_asteroid.x, y:new_asteroid.y, rot:new_asteroid.rot,section: new_asteroid.section}).ok();
-            asteroids.insert(new_ast, components::Asteroid{speed: 150.0, rot_speed: 150.0}).ok();
-            renderables.insert(new_ast, components::Renderable{
-                tex_name: "img/asteroid1.png".to_string(),
-                i_w: 100,
-                i_h: 100,
-                o_w: new_asteroid.size,
-                o_h: new_asteroid.size,
-                frame: 0,
-                total_frames: 1,
-                rot: 0.0
-            }).ok();
+            positions.insert(new_ast, components::Position{x:new_asteroid.x, y:new_asteroid.y, rot: 0.0}).ok();
+            velocities.insert(new_ast, velocity).ok();
+            spins.insert(new_ast, spin).ok();
+            asteroids.insert(new_ast, asteroid).ok();
+            renderables.insert(new_ast, renderable).ok();
+            colliders.insert(new_ast, collider).ok();
         }
 
-        let(_,_,_,_,mut game_data,_) = data;
-        for mut gamedata in (&mut game_data).join(){
-            gamedata.score += score;
-            let mut gamestate = crate::GAMESTATE.lock().unwrap();
-            if gamedata.score > gamestate.highscore {
-                gamestate.highscore = gamedata.score;
-            }
+        for (position, radius) in blasts {
+            let blast = entities.create();
+            positions.insert(blast, position).ok();
+            renderables.insert(blast, effect::ring((radius * 2.0) as u32)).ok();
+            effects.insert(blast, effect::ring_effect()).ok();
+        }
+
+        for (sound, x) in bangs {
+            sounds.play_at(sound, x);
+        }
+
+        for gamedata in (&mut game_data).join(){
+            game::add_score(gamedata, &mut best, score, *extra_life);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,64 @@
+use specs::{System, ReadStorage, WriteStorage, Read, Join, LendJoin};
+
+use crate::{components, game, GameMode};
+use crate::components::{AngularVelocity, Position, Velocity};
+
+impl Velocity {
+    // Moving `speed` pixels per second towards `heading` (degrees, clockwise from up)
+    pub fn from_heading(heading: f64, speed: f64) -> Self {
+        let radians = heading.to_radians();
+        Velocity {
+            x: speed * radians.sin(),
+            y: -speed * radians.cos()
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        (self.x * self.x + self.y * self.y).sqrt()
+    }
+
+    pub fn heading(&self) -> f64 {
+        normalize_angle(self.x.atan2(-self.y).to_degrees())
+    }
+}
+
+// Keeps an angle in degrees within 0..360
+pub fn normalize_angle(angle: f64) -> f64 {
+    angle.rem_euclid(360.0)
+}
+
+// Moves and spins everything with a velocity. The player always wraps around the
+// screen and everything else does in classic mode; in bounce mode what happens at
+// the edges is up to each entity's own systems.
+pub struct Mover;
+
+impl<'a> System<'a> for Mover {
+    type SystemData = (
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, AngularVelocity>,
+        ReadStorage<'a, components::Player>,
+        Read<'a, crate::DeltaTime>,
+        Read<'a, GameMode>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut positions, velocities, angular_velocities, players, deltatime, mode) = data;
+        let deltatime = deltatime.0;
+
+        for (pos, vel) in (&mut positions, &velocities).join() {
+            pos.x += vel.x * deltatime;
+            pos.y += vel.y * deltatime;
+        }
+
+        for (pos, spin) in (&mut positions, &angular_velocities).join() {
+            pos.rot = normalize_angle(pos.rot + spin.0 * deltatime);
+        }
+
+        for (pos, _, player) in (&mut positions, &velocities, (&players).maybe()).join() {
+            if mode.wraps() || player.is_some() {
+                game::wrap_position(pos);
+            }
+        }
+    }
+}
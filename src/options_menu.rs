@@ -0,0 +1,106 @@
+use asteroids_assignment::input::InputAction;
+use asteroids_assignment::settings::{AudioSettings, VOLUME_STEP};
+use asteroids_assignment::sound::Bus;
+
+#[derive(Clone, Copy, PartialEq)]
+enum OptionItem {
+    Volume(Bus),
+    Mute(Bus),
+    DuckMusic
+}
+
+const ITEMS: [OptionItem; 7] = [
+    OptionItem::Volume(Bus::Music),
+    OptionItem::Volume(Bus::Effects),
+    OptionItem::Volume(Bus::Ui),
+    OptionItem::Mute(Bus::Music),
+    OptionItem::Mute(Bus::Effects),
+    OptionItem::Mute(Bus::Ui),
+    OptionItem::DuckMusic
+];
+
+// What a key press in the menu did
+#[derive(PartialEq)]
+pub enum MenuResult {
+    Nothing,
+    Moved,
+    Changed,
+    Closed
+}
+
+// The in-game audio options. Thrust and Hyperspace move up and down the list,
+// the rotate keys turn a volume down or up and Fire or the rotate keys flip a switch.
+pub struct OptionsMenu {
+    pub open: bool,
+    pub paused_game: bool, //Opening the menu paused the game, so closing it resumes
+    selected: usize
+}
+
+impl OptionsMenu {
+    pub fn new() -> Self {
+        OptionsMenu {
+            open: false,
+            paused_game: false,
+            selected: 0
+        }
+    }
+
+    pub fn handle(&mut self, action: InputAction, audio: &mut AudioSettings) -> MenuResult {
+        let item = ITEMS[self.selected];
+        match (action, item) {
+            (InputAction::Options, _) | (InputAction::Quit, _) | (InputAction::Pause, _) => {
+                self.open = false;
+                MenuResult::Closed
+            },
+            (InputAction::Thrust, _) => {
+                self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len();
+                MenuResult::Moved
+            },
+            (InputAction::Hyperspace, _) => {
+                self.selected = (self.selected + 1) % ITEMS.len();
+                MenuResult::Moved
+            },
+            (InputAction::RotateLeft, OptionItem::Volume(bus)) => {
+                audio.set_volume(bus, audio.volume(bus) - VOLUME_STEP);
+                MenuResult::Changed
+            },
+            (InputAction::RotateRight, OptionItem::Volume(bus)) => {
+                audio.set_volume(bus, audio.volume(bus) + VOLUME_STEP);
+                MenuResult::Changed
+            },
+            (InputAction::RotateLeft, OptionItem::Mute(bus)) | (InputAction::RotateRight, OptionItem::Mute(bus)) | (InputAction::Fire, OptionItem::Mute(bus)) => {
+                audio.toggle_mute(bus);
+                MenuResult::Changed
+            },
+            (InputAction::RotateLeft, OptionItem::DuckMusic) | (InputAction::RotateRight, OptionItem::DuckMusic) | (InputAction::Fire, OptionItem::DuckMusic) => {
+                audio.duck_music = !audio.duck_music;
+                MenuResult::Changed
+            },
+            _ => MenuResult::Nothing
+        }
+    }
+
+    // One line per option, with the selected one marked
+    pub fn lines(&self, audio: &AudioSettings) -> Vec<String> {
+        ITEMS.iter().enumerate().map(|(index, item)| {
+            let text = match *item {
+                OptionItem::Volume(bus) => format!("{} volume  {:>3}%", bus_name(bus), (audio.volume(bus) * 100.0).round() as u32),
+                OptionItem::Mute(bus) => format!("{} muted  {}", bus_name(bus), on_off(audio.muted(bus))),
+                OptionItem::DuckMusic => format!("Duck music under explosions  {}", on_off(audio.duck_music))
+            };
+            if index == self.selected { format!("> {} <", text) } else { text }
+        }).collect()
+    }
+}
+
+fn bus_name(bus: Bus) -> &'static str {
+    match bus {
+        Bus::Music => "Music",
+        Bus::Effects => "Effects",
+        Bus::Ui => "Menu"
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "On" } else { "Off" }
+}
@@ -0,0 +1,238 @@
+use std::fs;
+
+use crate::{utils, GameMode, GameSettings};
+use crate::asteroid::AsteroidTiers;
+use crate::wave::Waves;
+use crate::input::{ActionState, InputAction};
+
+// Actions read by game::update, stored as the low bits of each tick's input
+pub const REPLAY_ACTIONS: [InputAction; 5] = [
+    InputAction::Thrust,
+    InputAction::RotateLeft,
+    InputAction::RotateRight,
+    InputAction::Fire,
+    InputAction::Hyperspace
+];
+
+// How hard a stick turns the ship (ActionState::rotate_steps), in the three bits above the actions
+const ROTATE_SHIFT: u16 = 5;
+const ROTATE_MASK: u16 = 0b111;
+
+// Commands that change the game world, stored in the high byte
+pub const CMD_TOGGLE_PAUSE: u16 = 1 << 8;
+pub const CMD_START_GAME: u16 = 1 << 9;
+pub const CMD_TOGGLE_INVINCIBILITY: u16 = 1 << 10;
+pub const CMD_THOUSAND_ASTEROIDS: u16 = 1 << 11;
+
+const MAGIC: &[u8; 4] = b"AREP";
+const VERSION: u8 = 8;
+const HEADER_LENGTH: usize = 44;
+
+// Seed and game settings plus one input word per simulation tick
+pub struct Replay {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub settings: GameSettings,
+    pub tiers: u64, //Fingerprint of the asteroid tier table, which has to match to play the replay back
+    pub waves: u64, //Fingerprint of the waves, likewise
+    pub inputs: Vec<u16>
+}
+
+impl Replay {
+    pub fn new(seed: u64, mode: GameMode) -> Self {
+        Replay {
+            seed,
+            mode,
+            settings: GameSettings::default(),
+            tiers: 0,
+            waves: 0,
+            inputs: Vec::new()
+        }
+    }
+
+    pub fn record(&mut self, key_manager: &ActionState, commands: u16){
+        let mut input = commands | ((key_manager.rotate_steps as u16 & ROTATE_MASK) << ROTATE_SHIFT);
+        for (bit, action) in REPLAY_ACTIONS.iter().enumerate() {
+            if utils::is_key_pressed(key_manager, action) {
+                input |= 1 << bit;
+            }
+        }
+        self.inputs.push(input);
+    }
+
+    // Sets the key manager to the recorded state for the tick and returns its commands
+    pub fn play(&self, tick: u64, key_manager: &mut ActionState) -> Option<u16> {
+        let input = *self.inputs.get(tick as usize)?;
+        for (bit, action) in REPLAY_ACTIONS.iter().enumerate() {
+            if input & (1 << bit) != 0 {
+                utils::key_down(key_manager, *action);
+            } else {
+                utils::key_up(key_manager, *action);
+            }
+        }
+        key_manager.rotate_steps = ((input >> ROTATE_SHIFT) & ROTATE_MASK) as u8;
+        Some(input & (CMD_TOGGLE_PAUSE | CMD_START_GAME | CMD_TOGGLE_INVINCIBILITY | CMD_THOUSAND_ASTEROIDS))
+    }
+
+    pub fn len(&self) -> u64 {
+        self.inputs.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    // Layout: "AREP", version, game mode (0 bounce, 1 classic), asteroid collisions (0 or 1),
+    // hyperspace risk (percent), points per extra life (u32 LE), seed (u64 LE), asteroid tiers
+    // fingerprint (u64 LE), waves fingerprint (u64 LE), tick count (u64 LE), then run-length
+    // encoded inputs as (run length u16 LE, input u16 LE) pairs
+    pub fn save(&self, path: &str) -> Result<(),String> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(match self.mode {
+            GameMode::Bounce => 0,
+            GameMode::Classic => 1
+        });
+        bytes.push(self.settings.asteroid_collisions as u8);
+        bytes.push(self.settings.hyperspace_risk);
+        bytes.extend_from_slice(&self.settings.extra_life_score.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.tiers.to_le_bytes());
+        bytes.extend_from_slice(&self.waves.to_le_bytes());
+        bytes.extend_from_slice(&self.len().to_le_bytes());
+
+        let mut i = 0;
+        while i < self.inputs.len() {
+            let input = self.inputs[i];
+            let mut run: u16 = 1;
+            while i + (run as usize) < self.inputs.len()
+                && self.inputs[i + run as usize] == input
+                && run < u16::MAX {
+                run += 1;
+            }
+            bytes.extend_from_slice(&run.to_le_bytes());
+            bytes.extend_from_slice(&input.to_le_bytes());
+            i += run as usize;
+        }
+
+        fs::write(path, bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Replay,String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        if bytes.len() < HEADER_LENGTH || &bytes[0..4] != MAGIC {
+            return Err(format!("{}: not a replay file", path));
+        }
+        if bytes[4] != VERSION {
+            return Err(format!("{}: unsupported replay version {}", path, bytes[4]));
+        }
+
+        let mode = match bytes[5] {
+            0 => GameMode::Bounce,
+            1 => GameMode::Classic,
+            other => return Err(format!("{}: unknown game mode {}", path, other))
+        };
+        let settings = GameSettings {
+            asteroid_collisions: bytes[6] != 0,
+            hyperspace_risk: bytes[7],
+            extra_life_score: u32::from_le_bytes(bytes[8..12].try_into().unwrap())
+        };
+        let seed = u64::from_le_bytes(bytes[12..20].try_into().unwrap());
+        let tiers = u64::from_le_bytes(bytes[20..28].try_into().unwrap());
+        let waves = u64::from_le_bytes(bytes[28..36].try_into().unwrap());
+        let ticks = u64::from_le_bytes(bytes[36..44].try_into().unwrap());
+
+        let mut inputs = Vec::new();
+        for run in bytes[HEADER_LENGTH..].chunks(4) {
+            if run.len() != 4 {
+                return Err(format!("{}: truncated replay", path));
+            }
+            let length = u16::from_le_bytes([run[0], run[1]]);
+            let input = u16::from_le_bytes([run[2], run[3]]);
+            inputs.extend(std::iter::repeat_n(input, length as usize));
+        }
+
+        if inputs.len() as u64 != ticks {
+            return Err(format!("{}: expected {} ticks but found {}", path, ticks, inputs.len()));
+        }
+
+        Ok(Replay{seed, mode, settings, tiers, waves, inputs})
+    }
+}
+
+// Identifies an asteroid tier table. FNV-1a over its contents, so unlike the std
+// hasher it comes out the same on every build and platform.
+pub fn tiers_fingerprint(tiers: &AsteroidTiers) -> u64 {
+    fingerprint(&format!("{:?}", tiers))
+}
+
+pub fn waves_fingerprint(waves: &Waves) -> u64 {
+    fingerprint(&format!("{:?}", waves))
+}
+
+fn fingerprint(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless, Simulation};
+
+    #[test]
+    fn saved_replay_plays_back_the_same_game() {
+        let mut sim = Simulation::new(7, GameMode::Classic);
+        sim.start_recording();
+        sim.start_game();
+        let mut key_manager = ActionState::new();
+        utils::key_down(&mut key_manager, InputAction::RotateRight);
+        for tick in 0..2400 {
+            if tick % 40 == 0 {
+                utils::key_down(&mut key_manager, InputAction::Fire);
+            } else if tick % 40 == 20 {
+                utils::key_up(&mut key_manager, InputAction::Fire);
+            }
+            if tick == 600 {
+                utils::key_down(&mut key_manager, InputAction::Thrust);
+            }
+            sim.step(&mut key_manager, crate::FIXED_TIMESTEP);
+        }
+        let recorded = headless::report(&sim.ecs, sim.seed(), sim.tick());
+
+        let path = std::env::temp_dir().join("asteroids_replay_round_trip.arep");
+        let path = path.to_str().unwrap();
+        sim.take_recording().unwrap().save(path).unwrap();
+        let replay = Replay::load(path).unwrap();
+        fs::remove_file(path).ok();
+
+        let played = headless::run_replay(Simulation::from_replay(replay, sim.asteroid_tiers(), sim.waves()).unwrap());
+        assert!(recorded.score > 0); //Otherwise an empty playback would pass too
+        assert_eq!(played, recorded);
+    }
+
+    #[test]
+    fn replay_with_other_asteroid_tiers_is_refused() {
+        let mut sim = Simulation::new(1234, GameMode::Bounce);
+        sim.start_recording();
+        let replay = sim.take_recording().unwrap();
+
+        let tiers = AsteroidTiers::from_toml("[rock]\nsprite = \"img/asteroid1.png\"\nsize = 80\nradius = 40.0\nspeed = [100.0, 150.0]\nspin = [0.0, 0.0]\npoints = 10\nspawn_weight = 1\n").unwrap();
+        assert!(Simulation::from_replay(replay, tiers, sim.waves()).is_err());
+    }
+
+    #[test]
+    fn replay_with_other_waves_is_refused() {
+        let mut sim = Simulation::new(1234, GameMode::Bounce);
+        sim.start_recording();
+        let replay = sim.take_recording().unwrap();
+
+        let waves = Waves::from_toml("[[wave]]\nasteroids = [{ tier = \"large\", count = 9 }]\n", &sim.asteroid_tiers()).unwrap();
+        assert!(Simulation::from_replay(replay, sim.asteroid_tiers(), waves).is_err());
+    }
+}
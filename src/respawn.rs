@@ -0,0 +1,129 @@
+use specs::prelude::*;
+use specs::{Entities, Join};
+
+use crate::{components, game, GameMode, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const RESPAWN_DELAY: f64 = 1.0; //Seconds off screen before the ship can come back
+const RESPAWN_MAX_WAIT: f64 = 3.0; //After this long the ship takes the safest spot instead of the centre
+pub const RESPAWN_SHIELD_TIME: f64 = 3.0;
+const SAFE_DISTANCE: f64 = 150.0; //Room needed between the ship and the nearest danger
+const SPOT_COLUMNS: u32 = 7;
+const SPOT_ROWS: u32 = 5;
+const BLINKS_PER_SECOND: f64 = 8.0;
+
+// The shielded ship blinks, hidden on every other beat
+pub fn blinked_out(player: &components::Player) -> bool {
+    player.shield > 0.0 && (player.shield * BLINKS_PER_SECOND) as u32 % 2 == 1
+}
+
+// Takes a ship that lost a life off the screen, then brings it back once there is
+// room for it, with a few seconds of shield against anything still nearby
+pub struct ShipRespawner;
+
+impl<'a> System<'a> for ShipRespawner {
+    type SystemData = (
+        WriteStorage<'a, components::Player>,
+        WriteStorage<'a, components::Position>,
+        WriteStorage<'a, components::Velocity>,
+        WriteStorage<'a, components::Renderable>,
+        WriteStorage<'a, components::Collider>,
+        ReadStorage<'a, components::Asteroid>,
+        ReadStorage<'a, components::Saucer>,
+        ReadStorage<'a, components::Missile>,
+        Entities<'a>,
+        Read<'a, crate::DeltaTime>,
+        Read<'a, GameMode>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut players, mut positions, mut velocities, mut renderables, mut colliders, asteroids, saucers, missiles, entities, deltatime, mode) = data;
+        let deltatime = deltatime.0;
+
+        //Everything that could kill a freshly spawned ship, as position and size
+        let mut dangers = Vec::<(components::Position, f64)>::new();
+        for (pos, collider, entity) in (&positions, &colliders, &entities).join() {
+            let hostile_missile = missiles.get(entity).is_some_and(|missile| missile.hostile);
+            if asteroids.contains(entity) || saucers.contains(entity) || hostile_missile {
+                dangers.push((components::Position{x: pos.x, y: pos.y, rot: 0.0}, collider.bounding_radius()));
+            }
+        }
+
+        for (player, entity) in (&mut players, &entities).join() {
+            player.shield = (player.shield - deltatime).max(0.0);
+
+            if player.died {
+                player.died = false;
+                player.lives -= 1;
+                player.respawning = true;
+                player.respawn_wait = 0.0;
+                player.hyperspace = 0.0;
+                player.impulse = vector2d::Vector2D::new(0.0,0.0);
+                if let Some(vel) = velocities.get_mut(entity) {
+                    vel.x = 0.0;
+                    vel.y = 0.0;
+                }
+                renderables.remove(entity);
+                colliders.remove(entity);
+                continue;
+            }
+
+            if !player.respawning {
+                continue;
+            }
+            player.respawn_wait += deltatime;
+            if player.respawn_wait < RESPAWN_DELAY {
+                continue;
+            }
+
+            let centre = components::Position{x: SCREEN_WIDTH as f64 / 2.0, y: SCREEN_HEIGHT as f64 / 2.0, rot: 0.0};
+            let spot = if clearance(*mode, &centre, &dangers) >= SAFE_DISTANCE {
+                centre
+            } else if player.respawn_wait >= RESPAWN_MAX_WAIT {
+                safest_spot(*mode, &dangers)
+            } else {
+                continue;
+            };
+
+            if let Some(pos) = positions.get_mut(entity) {
+                pos.x = spot.x;
+                pos.y = spot.y;
+                pos.rot = 0.0;
+            }
+            renderables.insert(entity, game::ship_renderable()).ok();
+            colliders.insert(entity, game::ship_collider()).ok();
+            player.respawning = false;
+            player.shield = RESPAWN_SHIELD_TIME;
+        }
+    }
+}
+
+// Distance from the spot to the edge of the nearest danger
+fn clearance(mode: GameMode, spot: &components::Position, dangers: &[(components::Position, f64)]) -> f64 {
+    dangers.iter()
+        .map(|(pos, radius)| {
+            let (diff_x, diff_y) = game::position_delta(mode, pos, spot);
+            (diff_x * diff_x + diff_y * diff_y).sqrt() - radius
+        })
+        .fold(f64::MAX, f64::min)
+}
+
+// The spot on a grid over the screen furthest from any danger
+fn safest_spot(mode: GameMode, dangers: &[(components::Position, f64)]) -> components::Position {
+    let mut best = components::Position{x: SCREEN_WIDTH as f64 / 2.0, y: SCREEN_HEIGHT as f64 / 2.0, rot: 0.0};
+    let mut best_clearance = clearance(mode, &best, dangers);
+    for column in 0..SPOT_COLUMNS {
+        for row in 0..SPOT_ROWS {
+            let spot = components::Position{
+                x: SCREEN_WIDTH as f64 * (column as f64 + 0.5) / SPOT_COLUMNS as f64,
+                y: SCREEN_HEIGHT as f64 * (row as f64 + 0.5) / SPOT_ROWS as f64,
+                rot: 0.0
+            };
+            let spot_clearance = clearance(mode, &spot, dangers);
+            if spot_clearance > best_clearance {
+                best = spot;
+                best_clearance = spot_clearance;
+            }
+        }
+    }
+    best
+}
@@ -0,0 +1,334 @@
+use specs::prelude::*;
+use specs::{World, WorldExt, Builder, Entities, Join};
+
+use rand::Rng;
+
+use crate::{assets, components, collision, game, wave, ExtraLifeScore, GameMode, GameRng, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::components::{Saucer, SaucerSize};
+use crate::collision::{CollisionEvent, CollisionEvents};
+use crate::sound::{Sound, SoundEvents};
+
+const LARGE_SIZE: u32 = 80;
+const SMALL_SIZE: u32 = 40;
+const LARGE_SPEED: f64 = 150.0;
+const SMALL_SPEED: f64 = 220.0;
+const LARGE_SCORE: u32 = 200;
+const SMALL_SCORE: u32 = 1000;
+const LARGE_FIRE_INTERVAL: f64 = 1.5;
+const SMALL_FIRE_INTERVAL: f64 = 1.0;
+const TURN_INTERVAL: f64 = 1.2;
+const SAUCER_MISSILE_SPEED: f64 = 450.0;
+const SMALL_AIM_ERROR: f64 = 10.0; //Degrees either side of a perfect shot
+
+// Outline of the saucer sprite at 100x100, scaled down to the saucer's size
+const SAUCER_HULL: [(f64, f64); 6] = [(-14.0, -28.0), (14.0, -28.0), (46.0, 4.0), (20.0, 22.0), (-20.0, 22.0), (-46.0, 4.0)];
+
+// Counts down to the next saucer while none is on screen. Each wave sets how often they come.
+pub struct SaucerSpawner {
+    pub timer: f64
+}
+
+impl SaucerSpawner {
+    pub fn new() -> Self {
+        SaucerSpawner {
+            timer: 0.0
+        }
+    }
+}
+
+impl Default for SaucerSpawner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn saucer_points(size: SaucerSize) -> u32 {
+    match size {
+        SaucerSize::Large => LARGE_SCORE,
+        SaucerSize::Small => SMALL_SCORE
+    }
+}
+
+fn siren(size: SaucerSize) -> Sound {
+    match size {
+        SaucerSize::Large => Sound::SaucerLarge,
+        SaucerSize::Small => Sound::SaucerSmall
+    }
+}
+
+pub fn update_spawner(ecs: &mut World, delta_time: f64){
+    {
+        let saucers = ecs.read_storage::<Saucer>();
+        if saucers.join().count() > 0 {
+            return;
+        }
+    }
+
+    let (interval, small_chance) = {
+        let current = ecs.read_resource::<wave::CurrentWave>();
+        (current.wave.saucer_interval, current.wave.small_saucer_chance)
+    };
+    if interval <= 0.0 {
+        return;
+    }
+
+    let spawn = {
+        let mut spawner = ecs.write_resource::<SaucerSpawner>();
+        spawner.timer -= delta_time;
+        if spawner.timer <= 0.0 {
+            spawner.timer = interval;
+            true
+        } else {
+            false
+        }
+    };
+
+    if spawn {
+        let (size, from_left, y) = {
+            let mut game_rng = ecs.write_resource::<GameRng>();
+            let rng = &mut game_rng.rng;
+            let size = if rng.gen_bool(small_chance) { SaucerSize::Small } else { SaucerSize::Large };
+            (size, rng.gen_bool(0.5), rng.gen_range(100.0..SCREEN_HEIGHT as f64 - 100.0))
+        };
+        create_saucer(ecs, size, from_left, y);
+    }
+}
+
+// Saucers enter from the left or right edge and head across the screen
+pub fn create_saucer(ecs: &mut World, size: SaucerSize, from_left: bool, y: f64){
+    let (saucer_size, speed, fire_interval) = match size {
+        SaucerSize::Large => (LARGE_SIZE, LARGE_SPEED, LARGE_FIRE_INTERVAL),
+        SaucerSize::Small => (SMALL_SIZE, SMALL_SPEED, SMALL_FIRE_INTERVAL)
+    };
+    let half = saucer_size as f64 / 2.0;
+    let (x, vel_x) = if from_left { (-half, speed) } else { (SCREEN_WIDTH as f64 + half, -speed) };
+    let scale = saucer_size as f64 / 100.0;
+    let hull = SAUCER_HULL.iter().map(|(px, py)| (px * scale, py * scale)).collect();
+
+    ecs.create_entity()
+        .with(components::Position{x, y, rot: 0.0})
+        .with(components::Velocity{x: vel_x, y: 0.0})
+        .with(components::Renderable{
+            tex_name: String::from(assets::SAUCER_TEXTURE),
+            i_w: 100,
+            i_h: 100,
+            o_w: saucer_size,
+            o_h: saucer_size,
+            frame: 0,
+            total_frames: 1
+        })
+        .with(Saucer{
+            size,
+            fire_timer: fire_interval,
+            turn_timer: TURN_INTERVAL,
+            travelled: 0.0
+        })
+        .with(components::Collider::polygon(hull, collision::LAYER_SAUCER,
+            collision::LAYER_PLAYER | collision::LAYER_ASTEROID | collision::LAYER_MISSILE))
+        .build();
+}
+
+// Zig-zags saucers across the screen, fires their missiles and plays their sirens
+pub struct SaucerPilot;
+
+impl<'a> System<'a> for SaucerPilot {
+    type SystemData = (
+        WriteStorage<'a, components::Position>,
+        WriteStorage<'a, components::Velocity>,
+        WriteStorage<'a, Saucer>,
+        ReadStorage<'a, components::Player>,
+        WriteStorage<'a, components::Renderable>,
+        WriteStorage<'a, components::Missile>,
+        WriteStorage<'a, components::Collider>,
+        Write<'a, SoundEvents>,
+        Entities<'a>,
+        WriteExpect<'a, GameRng>,
+        Read<'a, crate::DeltaTime>,
+        Read<'a, GameMode>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut positions, mut velocities, mut saucers, players, mut renderables, mut missiles, mut colliders, mut sounds, entities, mut game_rng, deltatime, mode) = data;
+        let deltatime = deltatime.0;
+        let rng = &mut game_rng.rng;
+
+        //Nothing to aim at while the ship is in hyperspace or respawning
+        let target = (&positions, &players).join()
+            .find(|(_, player)| game::in_play(player))
+            .map(|(pos, _)| components::Position{x: pos.x, y: pos.y, rot: 0.0});
+        let mut shots = Vec::<components::Position>::new();
+        let mut large_flying = false;
+        let mut small_flying = false;
+
+        for (pos, vel, saucer, rend, entity) in (&positions, &mut velocities, &mut saucers, &renderables, &entities).join() {
+            saucer.travelled += vel.x.abs() * deltatime;
+            if saucer.travelled > SCREEN_WIDTH as f64 + rend.o_w as f64 {
+                entities.delete(entity).ok();
+                continue;
+            }
+
+            let speed = vel.x.abs();
+            saucer.turn_timer -= deltatime;
+            if saucer.turn_timer <= 0.0 {
+                saucer.turn_timer = TURN_INTERVAL;
+                vel.y = match rng.gen_range(0..3) {
+                    0 => -speed / 2.0,
+                    1 => 0.0,
+                    _ => speed / 2.0
+                };
+            }
+            //Without wrapping they turn back at the top and bottom instead
+            let half_height = rend.o_h as f64 / 2.0;
+            if !mode.wraps() && ((pos.y < half_height && vel.y < 0.0) || (pos.y > SCREEN_HEIGHT as f64 - half_height && vel.y > 0.0)) {
+                vel.y = -vel.y;
+            }
+
+            saucer.fire_timer -= deltatime;
+            if saucer.fire_timer <= 0.0 {
+                let heading = match (saucer.size, &target) {
+                    (SaucerSize::Large, _) => {
+                        saucer.fire_timer = LARGE_FIRE_INTERVAL;
+                        Some(rng.gen_range(0.0..360.0))
+                    },
+                    (SaucerSize::Small, Some(target)) => {
+                        saucer.fire_timer = SMALL_FIRE_INTERVAL;
+                        let (diff_x, diff_y) = game::position_delta(*mode, target, pos);
+                        let aim = components::Velocity{x: diff_x, y: diff_y}.heading();
+                        Some(aim + rng.gen_range(-SMALL_AIM_ERROR..SMALL_AIM_ERROR))
+                    },
+                    (SaucerSize::Small, None) => None
+                };
+                if let Some(heading) = heading {
+                    shots.push(components::Position{x: pos.x, y: pos.y, rot: heading});
+                }
+            }
+
+            match saucer.size {
+                SaucerSize::Large => large_flying = true,
+                SaucerSize::Small => small_flying = true
+            }
+        }
+
+        for shot in shots {
+            sounds.play_at(Sound::Shoot, shot.x);
+
+            let missile = entities.create();
+            velocities.insert(missile, components::Velocity::from_heading(shot.rot, SAUCER_MISSILE_SPEED)).ok();
+            positions.insert(missile, shot).ok();
+            renderables.insert(missile, components::Renderable{
+                tex_name: String::from(assets::MISSILE_TEXTURE),
+                i_w: 100,
+                i_h: 100,
+                o_w: 25,
+                o_h: 25,
+                frame: 0,
+                total_frames: 1
+            }).ok();
+            missiles.insert(missile, components::Missile{
+                lifetime: game::MISSILE_LIFETIME,
+                hostile: true
+            }).ok();
+            colliders.insert(missile, components::Collider::circle(game::MISSILE_RADIUS, collision::LAYER_SAUCER_MISSILE,
+                collision::LAYER_PLAYER | collision::LAYER_ASTEROID)).ok();
+        }
+
+        //Sirens loop for as long as a saucer of their size is about, like the thrusters
+        for (size, flying) in [(SaucerSize::Large, large_flying), (SaucerSize::Small, small_flying)] {
+            if flying {
+                sounds.start_loop(siren(size));
+            } else {
+                sounds.stop(siren(size));
+            }
+        }
+    }
+}
+
+// Saucers hit by the player's missiles score bonus points. Saucers also
+// crash into asteroids and the ship, and their missiles can hit the ship.
+#[derive(Default)]
+pub struct SaucerCollider {
+    reader: Option<ReaderId<CollisionEvent>>
+}
+
+impl<'a> System<'a> for SaucerCollider {
+    type SystemData = (
+        ReadStorage<'a, Saucer>,
+        ReadStorage<'a, components::Missile>,
+        ReadStorage<'a, components::Asteroid>,
+        ReadStorage<'a, components::Position>,
+        WriteStorage<'a, components::Player>,
+        WriteStorage<'a, components::GameData>,
+        Write<'a, SoundEvents>,
+        Entities<'a>,
+        Read<'a, CollisionEvents>,
+        Read<'a, ExtraLifeScore>,
+        Write<'a, crate::BestScore>
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(collision::register_reader(world));
+    }
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (saucers, missiles, asteroids, positions, mut players, mut game_data, mut sounds, entities, events, extra_life, mut best) = data;
+        let reader = self.reader.as_mut().expect("SaucerCollider used without setup");
+        let mut score: u32 = 0;
+        let mut explosions = Vec::<f64>::new(); //x of each saucer that went up
+        let mut used = Vec::<Entity>::new(); //Each saucer and missile only counts once
+
+        for event in events.read(reader) {
+            if used.contains(&event.a) || used.contains(&event.b) {
+                continue;
+            }
+
+            if let Some((saucer_entity, missile_entity)) = event.matching(saucers.mask(), missiles.mask()) {
+                if missiles.get(missile_entity).is_none_or(|missile| missile.hostile) {
+                    continue;
+                }
+                if let Some(saucer) = saucers.get(saucer_entity) {
+                    score += saucer_points(saucer.size);
+                }
+                entities.delete(saucer_entity).ok();
+                entities.delete(missile_entity).ok();
+                used.push(saucer_entity);
+                used.push(missile_entity);
+                explosions.push(positions.get(saucer_entity).map_or(SCREEN_WIDTH as f64 / 2.0, |pos| pos.x));
+            } else if let Some((saucer_entity, _)) = event.matching(saucers.mask(), asteroids.mask()) {
+                entities.delete(saucer_entity).ok();
+                used.push(saucer_entity);
+                explosions.push(positions.get(saucer_entity).map_or(SCREEN_WIDTH as f64 / 2.0, |pos| pos.x));
+            } else if let Some((player_entity, saucer_entity)) = event.matching(players.mask(), saucers.mask()) {
+                if let Some(player) = players.get_mut(player_entity) {
+                    if game::is_invulnerable(player) {
+                        continue;
+                    }
+                    game::kill_player(player, player_entity, &entities);
+                }
+                entities.delete(saucer_entity).ok();
+                used.push(saucer_entity);
+                explosions.push(positions.get(saucer_entity).map_or(SCREEN_WIDTH as f64 / 2.0, |pos| pos.x));
+            } else if let Some((player_entity, missile_entity)) = event.matching(players.mask(), missiles.mask()) {
+                if !missiles.get(missile_entity).is_some_and(|missile| missile.hostile) {
+                    continue;
+                }
+                if let Some(player) = players.get_mut(player_entity) {
+                    if game::is_invulnerable(player) {
+                        continue;
+                    }
+                    game::kill_player(player, player_entity, &entities);
+                }
+                entities.delete(missile_entity).ok();
+                used.push(missile_entity);
+            }
+        }
+
+        for x in explosions {
+            sounds.play_at(Sound::ExplosionBig, x);
+        }
+
+        for gamedata in (&mut game_data).join() {
+            game::add_score(gamedata, &mut best, score, *extra_life);
+        }
+    }
+}
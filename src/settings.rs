@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::highscores;
+use crate::sound::Bus;
+
+pub const SETTINGS_FILENAME: &str = "settings.toml";
+pub const VOLUME_STEP: f64 = 0.1;
+
+// Mixer levels, from 0.0 (silent) to 1.0 (full volume)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub music_volume: f64,
+    pub effects_volume: f64,
+    pub ui_volume: f64,
+    pub music_muted: bool,
+    pub effects_muted: bool,
+    pub ui_muted: bool,
+    pub duck_music: bool //Turns the music down for a moment under explosions
+}
+
+impl AudioSettings {
+    pub fn volume(&self, bus: Bus) -> f64 {
+        match bus {
+            Bus::Music => self.music_volume,
+            Bus::Effects => self.effects_volume,
+            Bus::Ui => self.ui_volume
+        }
+    }
+
+    pub fn muted(&self, bus: Bus) -> bool {
+        match bus {
+            Bus::Music => self.music_muted,
+            Bus::Effects => self.effects_muted,
+            Bus::Ui => self.ui_muted
+        }
+    }
+
+    // What the bus actually plays at, taking mute into account
+    pub fn output_volume(&self, bus: Bus) -> f64 {
+        if self.muted(bus) { 0.0 } else { self.volume(bus) }
+    }
+
+    pub fn set_volume(&mut self, bus: Bus, volume: f64) {
+        let volume = (volume * 10.0).round().clamp(0.0, 10.0) / 10.0; //Whole steps, so repeated nudges land back on 0 and 1
+        match bus {
+            Bus::Music => self.music_volume = volume,
+            Bus::Effects => self.effects_volume = volume,
+            Bus::Ui => self.ui_volume = volume
+        }
+    }
+
+    pub fn toggle_mute(&mut self, bus: Bus) {
+        match bus {
+            Bus::Music => self.music_muted = !self.music_muted,
+            Bus::Effects => self.effects_muted = !self.effects_muted,
+            Bus::Ui => self.ui_muted = !self.ui_muted
+        }
+    }
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            music_volume: 0.6,
+            effects_volume: 1.0,
+            ui_volume: 0.8,
+            music_muted: false,
+            effects_muted: false,
+            ui_muted: false,
+            duck_music: true
+        }
+    }
+}
+
+// Everything the options menu changes, saved between runs
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioSettings
+}
+
+impl Settings {
+    // A missing file means the defaults, not an error
+    pub fn load(path: &Path) -> Result<Settings, String> {
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut settings: Settings = toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for bus in Bus::ALL {
+            let volume = settings.audio.volume(bus);
+            settings.audio.set_volume(bus, volume);
+        }
+        Ok(settings)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let contents = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+pub fn default_path() -> PathBuf {
+    highscores::data_dir().join(SETTINGS_FILENAME)
+}
@@ -0,0 +1,237 @@
+use specs::{World, WorldExt, Dispatcher};
+
+use crate::{game, GameMode, GameSettings};
+use crate::asteroid::{AsteroidTiers, ASTEROIDS_FILENAME};
+use crate::wave::{Waves, WAVES_FILENAME};
+use crate::input::ActionState;
+use crate::replay::{self, Replay};
+
+// Owns the game world and the systems that run on it, with no rendering or audio attached
+pub struct Simulation<'a, 'b> {
+    pub ecs: World,
+    dispatcher: Dispatcher<'a, 'b>,
+    accumulator: f64,
+    tick: u64,
+    pending_commands: u16,
+    recording: Option<Replay>,
+    playback: Option<Replay>
+}
+
+impl<'a, 'b> Simulation<'a, 'b> {
+    pub fn new(seed: u64, mode: GameMode) -> Self {
+        let mut ecs = World::new();
+        game::init_world(&mut ecs, seed, mode);
+        let mut dispatcher = game::build_dispatcher();
+        dispatcher.setup(&mut ecs);
+
+        Simulation {
+            ecs,
+            dispatcher,
+            accumulator: 0.0,
+            tick: 0,
+            pending_commands: 0,
+            recording: None,
+            playback: None
+        }
+    }
+
+    // Plays the replay back from its first tick, ignoring live input and commands.
+    // Fails if the asteroid tiers or waves aren't the ones it was recorded with, as
+    // the same input would then play out a different game.
+    pub fn from_replay(replay: Replay, tiers: AsteroidTiers, waves: Waves) -> Result<Self, String> {
+        if replay::tiers_fingerprint(&tiers) != replay.tiers {
+            return Err(format!("The replay was recorded with different asteroid types, put back the {} it was made with to play it", ASTEROIDS_FILENAME));
+        }
+        if replay::waves_fingerprint(&waves) != replay.waves {
+            return Err(format!("The replay was recorded with different waves, put back the {} it was made with to play it", WAVES_FILENAME));
+        }
+        let mut sim = Simulation::new(replay.seed, replay.mode);
+        sim.apply_settings(replay.settings);
+        sim.set_asteroid_tiers(tiers);
+        sim.set_waves(waves);
+        sim.playback = Some(replay);
+        Ok(sim)
+    }
+
+    // Records input from now on; start it before the first game so the replay covers it
+    pub fn start_recording(&mut self){
+        let mut recording = Replay::new(self.seed(), self.mode());
+        recording.settings = self.settings();
+        recording.tiers = replay::tiers_fingerprint(&self.ecs.read_resource::<AsteroidTiers>());
+        recording.waves = replay::waves_fingerprint(&self.ecs.read_resource::<Waves>());
+        self.recording = Some(recording);
+    }
+
+    // Asteroid physics, hyperspace risk and extra lives; set them before recording starts
+    pub fn apply_settings(&mut self, settings: GameSettings){
+        game::apply_settings(&mut self.ecs, settings);
+    }
+
+    pub fn settings(&self) -> GameSettings {
+        game::get_settings(&self.ecs)
+    }
+
+    pub fn asteroid_tiers(&self) -> AsteroidTiers {
+        (*self.ecs.read_resource::<AsteroidTiers>()).clone()
+    }
+
+    // Replaces the asteroid tier table; it takes effect when the next game starts
+    pub fn set_asteroid_tiers(&mut self, tiers: AsteroidTiers){
+        game::set_asteroid_tiers(&mut self.ecs, tiers);
+    }
+
+    pub fn waves(&self) -> Waves {
+        (*self.ecs.read_resource::<Waves>()).clone()
+    }
+
+    // Replaces the waves; they take effect when the next game starts
+    pub fn set_waves(&mut self, waves: Waves){
+        game::set_waves(&mut self.ecs, waves);
+    }
+
+    pub fn take_recording(&mut self) -> Option<Replay> {
+        self.recording.take()
+    }
+
+    // Runs as many fixed ticks as fit in the elapsed frame time and returns how far
+    // into the next tick we are (0..1), for interpolating the rendered positions
+    pub fn advance(&mut self, key_manager: &mut ActionState, frame_time: f64) -> f64 {
+        self.accumulator += frame_time.min(crate::MAX_FRAME_TIME);
+
+        while self.accumulator >= crate::FIXED_TIMESTEP {
+            self.step(key_manager, crate::FIXED_TIMESTEP);
+            self.accumulator -= crate::FIXED_TIMESTEP;
+        }
+
+        self.accumulator / crate::FIXED_TIMESTEP
+    }
+
+    // Advances the world by one frame: player input, then all systems
+    pub fn step(&mut self, key_manager: &mut ActionState, delta_time: f64){
+        let commands = match &self.playback {
+            Some(replay) => replay.play(self.tick, key_manager).unwrap_or(0),
+            None => std::mem::take(&mut self.pending_commands)
+        };
+        if let Some(recording) = &mut self.recording {
+            recording.record(key_manager, commands);
+        }
+        self.run_commands(commands);
+
+        if !self.ecs.read_resource::<crate::GameStatus>().runs_systems() {
+            //Nothing moves, so drawing mustn't keep blending from where things were before it stopped
+            game::store_previous_positions(&mut self.ecs);
+            self.tick += 1;
+            return;
+        }
+
+        game::store_previous_positions(&mut self.ecs);
+        self.ecs.write_resource::<crate::DeltaTime>().0 = delta_time;
+
+        game::update(&mut self.ecs, key_manager, delta_time);
+        self.dispatcher.dispatch(&self.ecs); //Runs the dispatcher and all systems run events
+        self.ecs.maintain(); //Removes all entities that have been deleted
+
+        self.tick += 1;
+    }
+
+    // Commands are applied at the start of the next tick so replays can reproduce them
+    // Starts a new game from the title or game over screen
+    pub fn start_game(&mut self){
+        self.pending_commands |= replay::CMD_START_GAME;
+    }
+
+    pub fn toggle_pause(&mut self){
+        self.pending_commands |= replay::CMD_TOGGLE_PAUSE;
+    }
+
+    pub fn toggle_invincibility(&mut self){
+        self.pending_commands |= replay::CMD_TOGGLE_INVINCIBILITY;
+    }
+
+    pub fn create_thousand_asteroids(&mut self){
+        self.pending_commands |= replay::CMD_THOUSAND_ASTEROIDS;
+    }
+
+    fn run_commands(&mut self, commands: u16){
+        if commands & replay::CMD_START_GAME != 0 {
+            game::start_game(&mut self.ecs);
+        }
+        if commands & replay::CMD_TOGGLE_PAUSE != 0 {
+            game::toggle_pause(&mut self.ecs);
+        }
+        if commands & replay::CMD_TOGGLE_INVINCIBILITY != 0 {
+            game::toggle_invincibility(&mut self.ecs);
+        }
+        if commands & replay::CMD_THOUSAND_ASTEROIDS != 0 {
+            game::create_thousand_asteroids(&mut self.ecs);
+        }
+    }
+
+    pub fn phase(&self) -> crate::GamePhase {
+        self.ecs.read_resource::<crate::GameStatus>().phase
+    }
+
+    pub fn is_playing_replay(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    // Ticks in the replay being played back, 0 when not playing one
+    pub fn replay_length(&self) -> u64 {
+        self.playback.as_ref().map_or(0, |replay| replay.len())
+    }
+
+    // True once a replay being played back has run out of recorded ticks
+    pub fn replay_finished(&self) -> bool {
+        match &self.playback {
+            Some(replay) => self.tick >= replay.len(),
+            None => false
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.ecs.read_resource::<crate::GameRng>().seed
+    }
+
+    pub fn mode(&self) -> GameMode {
+        *self.ecs.read_resource::<GameMode>()
+    }
+
+    pub fn best_score(&self) -> u32 {
+        self.ecs.read_resource::<crate::BestScore>().0
+    }
+
+    // The best score to beat, usually the top of the high score table
+    pub fn set_best_score(&mut self, score: u32){
+        self.ecs.insert(crate::BestScore(score));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::{Join, LendJoin};
+
+    use super::*;
+    use crate::components::{Position, PreviousPosition};
+
+    #[test]
+    fn paused_world_is_drawn_where_it_stopped() {
+        let mut sim = Simulation::new(3, GameMode::Bounce);
+        let mut key_manager = ActionState::new();
+        sim.start_game();
+        for _ in 0..10 {
+            sim.step(&mut key_manager, crate::FIXED_TIMESTEP);
+        }
+        sim.toggle_pause();
+        let alpha = sim.advance(&mut key_manager, crate::FIXED_TIMESTEP * 2.5);
+
+        let positions = sim.ecs.read_storage::<Position>();
+        let previous = sim.ecs.read_storage::<PreviousPosition>();
+        for (pos, prev) in (&positions, (&previous).maybe()).join() {
+            assert_eq!(game::interpolate_position(pos, prev, alpha), (pos.x, pos.y));
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// The mixer tracks sounds play through, each with its own volume
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bus {
+    Music,
+    Effects,
+    Ui
+}
+
+impl Bus {
+    pub const ALL: [Bus; 3] = [Bus::Music, Bus::Effects, Bus::Ui];
+}
+
+// Every sound the game can make
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sound {
+    Music,
+    Thruster,
+    Shoot,
+    Reload,
+    ExplosionBig,
+    ExplosionSmall,
+    SaucerLarge,
+    SaucerSmall,
+    Hyperspace,
+    ExtraLife,
+    Menu
+}
+
+impl Sound {
+    pub const ALL: [Sound; 11] = [Sound::Music, Sound::Thruster, Sound::Shoot, Sound::Reload, Sound::ExplosionBig,
+        Sound::ExplosionSmall, Sound::SaucerLarge, Sound::SaucerSmall, Sound::Hyperspace, Sound::ExtraLife, Sound::Menu];
+
+    pub fn filename(self) -> &'static str {
+        match self {
+            Sound::Music => crate::MUSIC_FILENAME,
+            Sound::Thruster => crate::THRUSTER_FILENAME,
+            Sound::Shoot => crate::SHOOT_FILENAME,
+            Sound::Reload | Sound::ExplosionBig | Sound::ExplosionSmall => crate::RELOAD_FILENAME, //The game has no separate explosion clip
+            Sound::SaucerLarge => crate::SAUCER_LARGE_FILENAME,
+            Sound::SaucerSmall => crate::SAUCER_SMALL_FILENAME,
+            Sound::Hyperspace => crate::HYPERSPACE_FILENAME,
+            Sound::ExtraLife => crate::EXTRA_LIFE_FILENAME,
+            Sound::Menu => crate::MENU_FILENAME
+        }
+    }
+
+    pub fn bus(self) -> Bus {
+        match self {
+            Sound::Music => Bus::Music,
+            Sound::Menu => Bus::Ui,
+            _ => Bus::Effects
+        }
+    }
+
+    // Loud enough to duck the music under
+    pub fn ducks_music(self) -> bool {
+        self == Sound::ExplosionBig
+    }
+
+    // Playback rate before any jitter; big explosions are the clip slowed down, small ones sped up
+    pub fn base_pitch(self) -> f64 {
+        match self {
+            Sound::ExplosionBig => 0.7,
+            Sound::ExplosionSmall => 1.4,
+            _ => 1.0
+        }
+    }
+
+    pub fn base_volume(self) -> f64 {
+        match self {
+            Sound::ExplosionSmall => 0.6,
+            _ => 1.0
+        }
+    }
+
+    // How much positioned plays of the sound vary, so repeats don't sound like one clip
+    pub fn jitter(self) -> Jitter {
+        match self {
+            Sound::Shoot | Sound::Reload => Jitter{pitch: 0.05, volume: 0.1},
+            Sound::ExplosionBig | Sound::ExplosionSmall => Jitter{pitch: 0.1, volume: 0.15},
+            Sound::Hyperspace => Jitter{pitch: 0.03, volume: 0.0},
+            _ => Jitter::NONE
+        }
+    }
+
+    // Most copies of the sound that play at once; more are dropped until one finishes
+    pub fn max_voices(self) -> usize {
+        match self {
+            Sound::Shoot | Sound::ExplosionBig | Sound::ExplosionSmall => 4,
+            Sound::Reload => 3,
+            Sound::ExtraLife => 1,
+            _ => 2
+        }
+    }
+
+    // Looped sounds keep going until stopped, the rest play once
+    pub fn looped(self) -> bool {
+        matches!(self, Sound::Music | Sound::Thruster | Sound::SaucerLarge | Sound::SaucerSmall)
+    }
+}
+
+// Random spread either side of a sound's pitch and volume, as a fraction of them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Jitter {
+    pub pitch: f64,
+    pub volume: f64
+}
+
+impl Jitter {
+    pub const NONE: Jitter = Jitter{pitch: 0.0, volume: 0.0};
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SoundEvent {
+    Play(Sound),
+    PlayAt(Sound, f64), //Panned by the x position it came from
+    Loop(Sound), //Starts the looped sound, or carries on if it is already going
+    Stop(Sound)
+}
+
+// Sounds asked for by the game since the last frame. Anything can push to it;
+// the game drains it once a frame and hands the events to the SoundManager.
+#[derive(Default)]
+pub struct SoundEvents {
+    events: Vec<SoundEvent>
+}
+
+impl SoundEvents {
+    pub fn play(&mut self, sound: Sound){
+        self.events.push(SoundEvent::Play(sound));
+    }
+
+    pub fn play_at(&mut self, sound: Sound, x: f64){
+        self.events.push(SoundEvent::PlayAt(sound, x));
+    }
+
+    pub fn start_loop(&mut self, sound: Sound){
+        self.events.push(SoundEvent::Loop(sound));
+    }
+
+    pub fn stop(&mut self, sound: Sound){
+        self.events.push(SoundEvent::Stop(sound));
+    }
+
+    pub fn drain(&mut self) -> Vec<SoundEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
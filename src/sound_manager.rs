@@ -1,97 +1,484 @@
-use kira::{
-    manager::{
-        AudioManager, AudioManagerSettings,
-        backend::cpal::CpalBackend,
-    },
-    sound::static_sound::{StaticSoundData, StaticSoundSettings, StaticSoundHandle, PlaybackState},
-    tween::Tween,
-    LoopBehavior,
-};
-use std::collections::HashMap;
-
-pub struct SoundManager {
-    pub sound_manager: AudioManager::<CpalBackend>,
-    pub sounds: HashMap::<String, StaticSoundData>,
-    pub loop_sounds: HashMap::<String, StaticSoundHandle>
+use std::collections::{HashMap, HashSet};
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::settings::AudioSettings;
+use crate::sound::{Bus, Jitter, Sound, SoundEvent};
+
+const DUCK_VOLUME: f64 = 0.35; //Share of its volume the music keeps while ducked
+const DUCK_TIME: f64 = 0.8; //Seconds the music stays down after an explosion
+const PAN_WIDTH: f64 = 0.8; //How far towards the speakers sounds at the screen edges are panned, 1 is all the way
+
+// How a single play of a sound comes out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayParams {
+    pub volume: f64,
+    pub pitch: f64, //Playback rate, so 2 is an octave up and twice as fast
+    pub panning: f64 //0 is hard left, 0.5 centred and 1 hard right
 }
 
-impl SoundManager {
-    pub fn new() -> Self {
-        Self {
-            sound_manager: AudioManager::<CpalBackend>::new(AudioManagerSettings::default()).expect("Failed to load Kira Audio Engine"),
-            sounds: HashMap::new(),
-            loop_sounds: HashMap::new(),
+impl Default for PlayParams {
+    fn default() -> Self {
+        PlayParams {
+            volume: 1.0,
+            pitch: 1.0,
+            panning: 0.5
         }
     }
+}
+
+// Something that can actually make the noise. Sounds are referred to by filename
+// and have to be loaded, onto the bus they play through, before they are played.
+pub trait AudioBackend {
+    // Returns the length of the sound in seconds
+    fn load(&mut self, filename: &str, looped: bool, bus: Bus) -> Result<f64, String>;
+    fn play(&mut self, filename: &str, params: PlayParams);
+    // Starts a looped sound, or resumes it if it was stopped; does nothing if it is already going
+    fn start_loop(&mut self, filename: &str);
+    fn stop(&mut self, filename: &str);
+    fn set_volume(&mut self, bus: Bus, volume: f64);
+    // The SoundManager's clock in seconds, passed on every update for backends that keep
+    // track of when things happen; the real ones don't need it
+    fn set_clock(&mut self, _clock: f64) {}
+}
+
+impl AudioBackend for Box<dyn AudioBackend> {
+    fn load(&mut self, filename: &str, looped: bool, bus: Bus) -> Result<f64, String> {
+        (**self).load(filename, looped, bus)
+    }
+
+    fn play(&mut self, filename: &str, params: PlayParams) {
+        (**self).play(filename, params)
+    }
+
+    fn start_loop(&mut self, filename: &str) {
+        (**self).start_loop(filename)
+    }
+
+    fn stop(&mut self, filename: &str) {
+        (**self).stop(filename)
+    }
+
+    fn set_volume(&mut self, bus: Bus, volume: f64) {
+        (**self).set_volume(bus, volume)
+    }
+
+    fn set_clock(&mut self, clock: f64) {
+        (**self).set_clock(clock)
+    }
+}
+
+// Plays the game's sound events through the backend and keeps the bus volumes
+// in line with the mixer settings, ducking the music under explosions. It also
+// keeps count of the copies of each sound still playing, to cap them.
+pub struct SoundManager<B: AudioBackend> {
+    pub backend: B,
+    mixer: AudioSettings,
+    duck: f64, //Seconds left with the music ducked
+    clock: f64, //Seconds of update() so far
+    lengths: HashMap<String, f64>, //Seconds, by filename
+    missing: HashSet<String>, //Sounds that failed to load and are left silent
+    voices: HashMap<String, Vec<f64>>, //When each playing copy of a clip ends, by filename, as some sounds share a clip
+    rng: StdRng //Only for jitter, separate from the game's so it can't change the gameplay
+}
+
+impl<B: AudioBackend> SoundManager<B> {
+    pub fn new(backend: B) -> Self {
+        let mut manager = Self {
+            backend,
+            mixer: AudioSettings::default(),
+            duck: 0.0,
+            clock: 0.0,
+            lengths: HashMap::new(),
+            missing: HashSet::new(),
+            voices: HashMap::new(),
+            rng: StdRng::from_entropy()
+        };
+        manager.update_volumes();
+        manager
+    }
+
+    // Carries out one event from the game's sound queue
+    pub fn handle(&mut self, event: SoundEvent) {
+        match event {
+            SoundEvent::Play(sound) => self.play_sound(sound),
+            SoundEvent::PlayAt(sound, x) => self.play_sound_at(sound, x),
+            SoundEvent::Loop(sound) if !self.missing.contains(sound.filename()) => self.backend.start_loop(sound.filename()),
+            SoundEvent::Stop(sound) if !self.missing.contains(sound.filename()) => self.backend.stop(sound.filename()),
+            _ => {}
+        }
+    }
+
+    // Centred and unvaried, for sounds that don't come from anywhere on screen
+    pub fn play_sound(&mut self, sound: Sound) {
+        self.play_sound_varied(sound, None, Jitter::NONE);
+    }
+
+    // Panned by where on screen it happened, varied by the sound's own jitter
+    pub fn play_sound_at(&mut self, sound: Sound, x: f64) {
+        self.play_sound_varied(sound, Some(x), sound.jitter());
+    }
+
+    pub fn play_sound_varied(&mut self, sound: Sound, x: Option<f64>, jitter: Jitter) {
+        if self.missing.contains(sound.filename()) {
+            return;
+        }
+        let clock = self.clock;
+        let voices = self.voices.entry(sound.filename().to_string()).or_default();
+        voices.retain(|end| *end > clock);
+        if voices.len() >= sound.max_voices() {
+            return;
+        }
 
-    pub fn play_sound(&mut self, filename: String) {
-        if self.sounds.contains_key(&filename){
-            if let Some(x) = self.sounds.get_mut(&filename) {
-                self.sound_manager.play(x.clone()).expect("Failed to play sounds");
+        let pitch = sound.base_pitch() * (1.0 + self.rng.gen_range(-jitter.pitch..=jitter.pitch));
+        let volume = sound.base_volume() * (1.0 + self.rng.gen_range(-jitter.volume..=jitter.volume));
+        let panning = x.map_or(0.5, |x| 0.5 + (x / crate::SCREEN_WIDTH as f64 - 0.5).clamp(-0.5, 0.5) * PAN_WIDTH);
+        self.backend.play(sound.filename(), PlayParams{volume, pitch, panning});
+
+        let length = self.lengths.get(sound.filename()).copied().unwrap_or(0.0);
+        voices.push(clock + length / pitch);
+
+        if sound.ducks_music() && self.mixer.duck_music {
+            let was_ducked = self.duck > 0.0;
+            self.duck = DUCK_TIME;
+            if !was_ducked {
+                self.update_volumes();
             }
-        }else{
-            println!("Sound doesn't exist");
         }
     }
 
-    pub fn start_sound(&mut self, filename: &String) {
-        match self.sounds.get_mut(filename) {
-            Some(x) => {
-                match self.sound_manager.play(x.clone()) {
-                    Ok(res) => {
-                        self.loop_sounds.entry( filename.to_string() )
-                            .or_insert(res);
-                    },
-                    Err(e) => {
-                        println!("{}", e);
-                    }
-                }
+    // Moves the clock the voice limit goes by on and brings the music back up
+    // once the ducking runs out; call once a frame
+    pub fn update(&mut self, delta_time: f64) {
+        self.clock += delta_time;
+        self.backend.set_clock(self.clock);
+        if self.duck <= 0.0 {
+            return;
+        }
+        self.duck -= delta_time;
+        if self.duck <= 0.0 {
+            self.duck = 0.0;
+            self.update_volumes();
+        }
+    }
+
+    pub fn mixer(&self) -> AudioSettings {
+        self.mixer
+    }
+
+    pub fn set_mixer(&mut self, mixer: AudioSettings) {
+        self.mixer = mixer;
+        if !mixer.duck_music {
+            self.duck = 0.0;
+        }
+        self.update_volumes();
+    }
+
+    fn update_volumes(&mut self) {
+        for bus in Bus::ALL {
+            let mut volume = self.mixer.output_volume(bus);
+            if bus == Bus::Music && self.duck > 0.0 {
+                volume *= DUCK_VOLUME;
+            }
+            self.backend.set_volume(bus, volume);
+        }
+    }
+
+    // A sound that fails to load is kept silent from then on, so the error only comes up once
+    pub fn load_sound(&mut self, filename: &str, looped: bool, bus: Bus) -> Result<(), String> {
+        match self.backend.load(filename, looped, bus) {
+            Ok(length) => {
+                self.lengths.insert(filename.to_string(), length);
+                self.missing.remove(filename);
+                Ok(())
             },
-            None => {
-                println!("Error when playing music");
+            Err(e) => {
+                self.missing.insert(filename.to_string());
+                Err(e)
             }
         }
     }
+}
 
-    pub fn stop_sound(&mut self, filename: &String) {
-        if self.loop_sounds.contains_key(&filename.to_string()){
-            if let Some(x) = self.loop_sounds.get_mut(&filename.to_string()) {
-                if x.state() == PlaybackState::Playing {
-                    // Pausing sound
-                    x.pause(Tween::default()).expect("Failed to pause sound");
+// The sound card if there is one, otherwise silence so the game still runs
+#[cfg(feature = "kira")]
+pub fn default_backend() -> Box<dyn AudioBackend> {
+    match CpalBackend::new() {
+        Ok(backend) => Box::new(backend),
+        Err(e) => {
+            println!("No audio device ({}), playing without sound", e);
+            Box::new(NullBackend)
+        }
+    }
+}
+
+// Plays nothing, for machines without a sound card
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn load(&mut self, _filename: &str, _looped: bool, _bus: Bus) -> Result<f64, String> {
+        Ok(0.0)
+    }
+
+    fn play(&mut self, _filename: &str, _params: PlayParams) {}
+
+    fn start_loop(&mut self, _filename: &str) {}
+
+    fn stop(&mut self, _filename: &str) {}
+
+    fn set_volume(&mut self, _bus: Bus, _volume: f64) {}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SoundCall {
+    Play(String, PlayParams),
+    Loop(String),
+    Stop(String),
+    Volume(Bus, f64)
+}
+
+// Plays nothing but writes down every call, with the SoundManager's clock at the time,
+// so a test can check which sounds went off and when
+pub struct RecordingBackend {
+    clock: f64,
+    pub sound_length: f64, //Seconds every loaded sound is taken to last, for the voice limit
+    pub loaded: Vec<String>,
+    pub calls: Vec<(f64, SoundCall)>
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        RecordingBackend {
+            clock: 0.0,
+            sound_length: 0.5,
+            loaded: Vec::new(),
+            calls: Vec::new()
+        }
+    }
+
+    fn record(&mut self, call: SoundCall) {
+        self.calls.push((self.clock, call));
+    }
+}
+
+impl Default for RecordingBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioBackend for RecordingBackend {
+    fn load(&mut self, filename: &str, _looped: bool, _bus: Bus) -> Result<f64, String> {
+        self.loaded.push(filename.to_string());
+        Ok(self.sound_length)
+    }
+
+    fn play(&mut self, filename: &str, params: PlayParams) {
+        self.record(SoundCall::Play(filename.to_string(), params));
+    }
+
+    fn start_loop(&mut self, filename: &str) {
+        self.record(SoundCall::Loop(filename.to_string()));
+    }
+
+    fn stop(&mut self, filename: &str) {
+        self.record(SoundCall::Stop(filename.to_string()));
+    }
+
+    fn set_volume(&mut self, bus: Bus, volume: f64) {
+        self.record(SoundCall::Volume(bus, volume));
+    }
+
+    fn set_clock(&mut self, clock: f64) {
+        self.clock = clock;
+    }
+}
+
+#[cfg(feature = "kira")]
+pub use self::cpal::CpalBackend;
+
+#[cfg(feature = "kira")]
+mod cpal {
+    use std::collections::HashMap;
+
+    use kira::{
+        manager::{
+            AudioManager, AudioManagerSettings,
+            backend::cpal::CpalBackend as KiraCpalBackend,
+        },
+        sound::static_sound::{StaticSoundData, StaticSoundSettings, StaticSoundHandle, PlaybackState},
+        track::{TrackBuilder, TrackHandle},
+        track::effect::{delay::DelayBuilder, reverb::ReverbBuilder},
+        tween::Tween,
+        LoopBehavior,
+        Volume,
+    };
+
+    use super::{AudioBackend, PlayParams};
+    use crate::sound::Bus;
+
+    // Plays through the system's default output device, with a mixer sub-track per bus
+    pub struct CpalBackend {
+        pub sound_manager: AudioManager::<KiraCpalBackend>,
+        pub sounds: HashMap::<String, StaticSoundData>,
+        pub loop_sounds: HashMap::<String, StaticSoundHandle>,
+        pub tracks: HashMap::<Bus, TrackHandle>
+    }
+
+    impl CpalBackend {
+        pub fn new() -> Result<Self, String> {
+            let mut sound_manager = AudioManager::<KiraCpalBackend>::new(AudioManagerSettings::default()).map_err(|e| e.to_string())?;
+
+            let mut tracks = HashMap::new();
+            for bus in Bus::ALL {
+                let mut builder = TrackBuilder::new();
+                if bus == Bus::Effects {
+                    //A short echo and a little room so explosions ring out
+                    builder.add_effect(DelayBuilder::new().delay_time(0.12).feedback(Volume::Decibels(-9.0)).mix(0.15));
+                    builder.add_effect(ReverbBuilder::new().feedback(0.6).damping(0.5).mix(0.12));
+                }
+                let track = sound_manager.add_sub_track(builder).map_err(|e| e.to_string())?;
+                tracks.insert(bus, track);
+            }
+
+            Ok(Self {
+                sound_manager,
+                sounds: HashMap::new(),
+                loop_sounds: HashMap::new(),
+                tracks,
+            })
+        }
+
+        fn start_sound(&mut self, filename: &str) {
+            match self.sounds.get_mut(filename) {
+                Some(x) => {
+                    match self.sound_manager.play(x.clone()) {
+                        Ok(res) => {
+                            self.loop_sounds.entry( filename.to_string() )
+                                .or_insert(res);
+                        },
+                        Err(e) => {
+                            println!("{}", e);
+                        }
+                    }
+                },
+                None => {
+                    println!("Error when playing music");
                 }
             }
         }
     }
 
-    pub fn resume_sound(&mut self,filename: &String) {
-        if self.loop_sounds.contains_key(&filename.to_string()) {
-            if let Some(x) = self.loop_sounds.get_mut(&filename.to_string()) {
+    impl AudioBackend for CpalBackend {
+        fn load(&mut self, filename: &str, looped: bool, bus: Bus) -> Result<f64, String> {
+            let mut sss = StaticSoundSettings::default();
+            if looped {
+                sss.loop_behavior = Some(LoopBehavior{start_position:0.0});
+            }
+            if let Some(track) = self.tracks.get(&bus) {
+                sss = sss.track(track);
+            }
+
+            let data = StaticSoundData::from_file(filename, sss).map_err(|e| e.to_string())?;
+            let length = data.duration().as_secs_f64();
+            self.sounds.entry( filename.to_string() ).or_insert(data);
+            Ok(length)
+        }
+
+        fn play(&mut self, filename: &str, params: PlayParams) {
+            if let Some(x) = self.sounds.get_mut(filename) {
+                let settings = x.settings.volume(params.volume).playback_rate(params.pitch).panning(params.panning);
+                if let Err(e) = self.sound_manager.play(x.with_settings(settings)) {
+                    println!("Failed to play {}: {}", filename, e);
+                }
+            }else{
+                println!("Sound doesn't exist");
+            }
+        }
+
+        fn start_loop(&mut self, filename: &str) {
+            if let Some(x) = self.loop_sounds.get_mut(filename) {
                 if x.state() == PlaybackState::Paused  {
                     // Resuming sound
-                    x.resume(Tween::default()).expect("Failed to resume sound");
+                    x.resume(Tween::default()).ok();
                 } else if x.state() == PlaybackState::Stopped {
                     // Restarting sound after it has stopped
-                    x.resume(Tween::default()).expect("Failed to restart sound");
+                    x.resume(Tween::default()).ok();
+                }
+            }else{
+                // Starting the sound for the first time
+                self.start_sound(filename);
+            }
+        }
+
+        fn stop(&mut self, filename: &str) {
+            if let Some(x) = self.loop_sounds.get_mut(filename) {
+                if x.state() == PlaybackState::Playing {
+                    // Pausing sound
+                    x.pause(Tween::default()).ok();
                 }
             }
-        }else{
-            // Starting the sound for the first time
-            self.start_sound(filename);
+        }
+
+        fn set_volume(&mut self, bus: Bus, volume: f64) {
+            if let Some(track) = self.tracks.get(&bus) {
+                track.set_volume(volume, Tween::default()).ok();
+            }
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{ActionState, InputAction};
+    use specs::WorldExt;
+    use crate::sound::SoundEvents;
+    use crate::{utils, GameMode, Simulation, FIXED_TIMESTEP, SHOOT_FILENAME};
 
-    pub fn load_sound(&mut self, filename: &String, looped: bool) {
-        let mut sss = StaticSoundSettings::default();
-        if looped {
-            sss.loop_behavior = Some(LoopBehavior{start_position:0.0});
+    #[test]
+    fn firing_plays_the_shoot_sound() {
+        let mut sim = Simulation::new(99, GameMode::Bounce);
+        sim.start_game();
+        let mut sound_manager = SoundManager::new(RecordingBackend::new());
+        let mut key_manager = ActionState::new();
+        let fire_tick = 60;
+
+        for tick in 0..120 {
+            if tick == fire_tick {
+                utils::key_down(&mut key_manager, InputAction::Fire);
+            }
+            sim.step(&mut key_manager, FIXED_TIMESTEP);
+            for event in sim.ecs.write_resource::<SoundEvents>().drain() {
+                sound_manager.handle(event);
+            }
+            sound_manager.update(FIXED_TIMESTEP);
+        }
+
+        let shots: Vec<f64> = sound_manager.backend.calls.iter()
+            .filter(|(_, call)| matches!(call, SoundCall::Play(filename, _) if filename == SHOOT_FILENAME))
+            .map(|(clock, _)| *clock)
+            .collect();
+        //One shot, stamped with the clock of the tick the missile was fired on
+        assert_eq!(shots.len(), 1);
+        assert!((shots[0] - fire_tick as f64 * FIXED_TIMESTEP).abs() < FIXED_TIMESTEP / 2.0);
+    }
+
+    #[test]
+    fn sounds_sharing_a_clip_share_its_voices() {
+        let mut sound_manager = SoundManager::new(RecordingBackend::new());
+        for sound in [Sound::Reload, Sound::ExplosionBig, Sound::ExplosionSmall] {
+            sound_manager.load_sound(sound.filename(), sound.looped(), sound.bus()).unwrap();
+        }
+        for sound in [Sound::Reload, Sound::ExplosionBig, Sound::ExplosionSmall] {
+            for _ in 0..sound.max_voices() {
+                sound_manager.play_sound(sound);
+            }
         }
 
-        self.sounds.entry( (&filename).to_string() ).or_insert(
-            StaticSoundData::from_file(
-                filename,
-                sss
-            ).expect("Failed to load sound")
-        );
+        let plays = sound_manager.backend.calls.iter().filter(|(_, call)| matches!(call, SoundCall::Play(..))).count();
+        let most = [Sound::Reload, Sound::ExplosionBig, Sound::ExplosionSmall].iter().map(|sound| sound.max_voices()).max().unwrap();
+        assert!(plays <= most);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,151 @@
+use specs::{System, ReadStorage, Write, Read, Join, Entity};
+use specs::prelude::Entities;
+
+use crate::{components, GameMode, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Width and height of a grid cell in pixels, about the size of the biggest asteroid
+pub const CELL_SIZE: f64 = 120.0;
+
+// Buckets entities into fixed size cells by their bounding circle so collision
+// systems only have to check what is nearby. Rebuilt from scratch every tick.
+pub struct SpatialGrid {
+    columns: i32,
+    rows: i32,
+    cells: Vec<Vec<Entity>>,
+    wraps: bool //Cells past one edge continue on the opposite edge
+}
+
+impl SpatialGrid {
+    pub fn new() -> Self {
+        let columns = (SCREEN_WIDTH as f64 / CELL_SIZE).ceil() as i32;
+        let rows = (SCREEN_HEIGHT as f64 / CELL_SIZE).ceil() as i32;
+        SpatialGrid {
+            columns,
+            rows,
+            cells: vec![Vec::new(); (columns * rows) as usize],
+            wraps: false
+        }
+    }
+
+    pub fn clear(&mut self, mode: GameMode){
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+        self.wraps = mode.wraps();
+    }
+
+    // Adds the entity to every cell its bounding circle overlaps
+    pub fn insert(&mut self, entity: Entity, x: f64, y: f64, radius: f64){
+        for index in self.cells_overlapping(x, y, radius) {
+            self.cells[index].push(entity);
+        }
+    }
+
+    // Every entity sharing a cell with the circle, each listed once
+    pub fn query(&self, x: f64, y: f64, radius: f64) -> Vec<Entity> {
+        let mut found = Vec::new();
+        for index in self.cells_overlapping(x, y, radius) {
+            found.extend_from_slice(&self.cells[index]);
+        }
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    fn cells_overlapping(&self, x: f64, y: f64, radius: f64) -> Vec<usize> {
+        let first_column = ((x - radius) / CELL_SIZE).floor() as i32;
+        let last_column = ((x + radius) / CELL_SIZE).floor() as i32;
+        let first_row = ((y - radius) / CELL_SIZE).floor() as i32;
+        let last_row = ((y + radius) / CELL_SIZE).floor() as i32;
+
+        let mut indices = Vec::new();
+        for column in first_column..=last_column {
+            for row in first_row..=last_row {
+                let index = self.cell_index(column, row);
+                if !indices.contains(&index) {
+                    indices.push(index);
+                }
+            }
+        }
+        indices
+    }
+
+    // Cells off the screen wrap round when the screen does, otherwise they belong to the edge cells
+    fn cell_index(&self, column: i32, row: i32) -> usize {
+        let (column, row) = if self.wraps {
+            (column.rem_euclid(self.columns), row.rem_euclid(self.rows))
+        } else {
+            (column.clamp(0, self.columns - 1), row.clamp(0, self.rows - 1))
+        };
+        (row * self.columns + column) as usize
+    }
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct SpatialGridBuilder;
+
+impl<'a> System<'a> for SpatialGridBuilder {
+    type SystemData = (
+        ReadStorage<'a, components::Position>,
+        ReadStorage<'a, components::Collider>,
+        Entities<'a>,
+        Write<'a, SpatialGrid>,
+        Read<'a, GameMode>
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (positions, colliders, entities, mut grid, mode) = data;
+        grid.clear(*mode);
+        for (pos, collider, entity) in (&positions, &colliders, &entities).join() {
+            grid.insert(entity, pos.x, pos.y, collider.bounding_radius());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::{Builder, World, WorldExt};
+
+    use super::*;
+
+    // Two entities in a grid cleared for the mode, for querying around the first
+    fn grid_with(mode: GameMode, a: (f64, f64), b: (f64, f64)) -> (SpatialGrid, Entity, Entity) {
+        let mut world = World::new();
+        let first = world.create_entity().build();
+        let second = world.create_entity().build();
+        let mut grid = SpatialGrid::new();
+        grid.clear(mode);
+        grid.insert(first, a.0, a.1, 10.0);
+        grid.insert(second, b.0, b.1, 10.0);
+        (grid, first, second)
+    }
+
+    #[test]
+    fn pair_straddling_a_cell_boundary_is_found() {
+        let (grid, first, second) = grid_with(GameMode::Bounce, (CELL_SIZE - 5.0, 60.0), (CELL_SIZE + 8.0, 60.0));
+        assert_eq!(grid.query(CELL_SIZE - 5.0, 60.0, 10.0), vec![first, second]);
+    }
+
+    #[test]
+    fn pair_across_the_wrap_edge_is_found_in_classic_mode() {
+        let left = (5.0, 500.0);
+        let right = (SCREEN_WIDTH as f64 - 5.0, 500.0);
+        let (grid, first, second) = grid_with(GameMode::Classic, left, right);
+        assert_eq!(grid.query(left.0, left.1, 10.0), vec![first, second]);
+
+        //Bouncing asteroids can't reach each other across the screen
+        let (grid, first, _) = grid_with(GameMode::Bounce, left, right);
+        assert_eq!(grid.query(left.0, left.1, 10.0), vec![first]);
+    }
+
+    #[test]
+    fn pair_too_far_apart_is_not_found() {
+        let (grid, first, _) = grid_with(GameMode::Classic, (100.0, 100.0), (600.0, 600.0));
+        assert_eq!(grid.query(100.0, 100.0, 10.0), vec![first]);
+    }
+}
@@ -45,6 +45,11 @@ impl<'l, K, R, L> ResourceManager<'l, K, R, L>
             Ok,
         )
     }
+
+    // Puts a resource in the cache under a key, so later loads of it get that instead
+    pub fn insert(&mut self, key: K, resource: Rc<R>) {
+        self.cache.insert(key, resource);
+    }
 }
 
 // Generic trait to Load any Resource Kind
@@ -1,26 +1,26 @@
-use std::collections::HashMap;
+use crate::input::{ActionState, InputAction};
 
 // Key Manager Functions
-pub fn key_down(key_manager: &mut HashMap<String, bool>, keyname: String){
-    if !key_manager.contains_key(&keyname){
-        key_manager.entry(keyname).or_insert(true);
+pub fn key_down(key_manager: &mut ActionState, keyname: InputAction){
+    if !key_manager.held.contains_key(&keyname){
+        key_manager.held.entry(keyname).or_insert(true);
     }else{
-        if let Some(x) = key_manager.get_mut(&keyname) {
+        if let Some(x) = key_manager.held.get_mut(&keyname) {
             *x = true;
         }
-    } 
+    }
 }
 
-pub fn key_up(key_manager:&mut HashMap<String, bool>, keyname: String){
-    if !key_manager.contains_key(&keyname){
-        key_manager.entry(keyname).or_insert(false);
+pub fn key_up(key_manager:&mut ActionState, keyname: InputAction){
+    if !key_manager.held.contains_key(&keyname){
+        key_manager.held.entry(keyname).or_insert(false);
     }else{
-        if let Some(x) = key_manager.get_mut(&keyname) {
+        if let Some(x) = key_manager.held.get_mut(&keyname) {
             *x = false;
         }
     }
 }
 
-pub fn is_key_pressed(key_manager: &HashMap<String, bool>, value: &str) -> bool {
-    key_manager.contains_key(&value.to_string()) && key_manager.get(&value.to_string())==Some(&true)
-}
\ No newline at end of file
+pub fn is_key_pressed(key_manager: &ActionState, value: &InputAction) -> bool {
+    key_manager.held.contains_key(value) && key_manager.held.get(value)==Some(&true)
+}
@@ -0,0 +1,281 @@
+use std::fs;
+use std::path::Path;
+
+use specs::{World, WorldExt, Join};
+
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
+
+use crate::{components, game, saucer, GameMode, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::asteroid::AsteroidTiers;
+
+pub const WAVES_FILENAME: &str = "waves.toml";
+const SPAWN_MARGIN: f64 = 50.0; //Keeps new asteroids off the screen edges
+const SPAWN_CLEARANCE: f64 = 150.0; //New asteroids never start closer than this to the ship
+
+// A group of asteroids of one type in a wave
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaveAsteroids {
+    pub tier: String,
+    pub count: u32
+}
+
+// One level's worth of asteroids and saucers, see waves.toml for what each field does
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Wave {
+    pub asteroids: Vec<WaveAsteroids>,
+    #[serde(default = "default_speed_scale")]
+    pub speed_scale: f64,
+    #[serde(default)]
+    pub saucer_interval: f64,
+    #[serde(default)]
+    pub small_saucer_chance: f64,
+    #[serde(default)]
+    pub time_limit: f64
+}
+
+fn default_speed_scale() -> f64 {
+    1.0
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Wave {
+            asteroids: Vec::new(),
+            speed_scale: 1.0,
+            saucer_interval: 0.0,
+            small_saucer_chance: 0.0,
+            time_limit: 0.0
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WaveFile {
+    #[serde(default)]
+    wave: Vec<Wave>
+}
+
+// The wave being played and how long is left to clear it
+#[derive(Default)]
+pub struct CurrentWave {
+    pub wave: Wave,
+    pub time_left: f64
+}
+
+// The defined waves in level order; levels past the last one are generated
+#[derive(Clone, Debug)]
+pub struct Waves {
+    waves: Vec<Wave>
+}
+
+impl Waves {
+    // Parses and checks a list of [[wave]] sections against the asteroid types, reporting every problem at once
+    pub fn from_toml(contents: &str, tiers: &AsteroidTiers) -> Result<Waves, String> {
+        let file: WaveFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+        let mut errors = Vec::new();
+
+        for (index, wave) in file.wave.iter().enumerate() {
+            let level = index + 1;
+            if wave.asteroids.iter().map(|group| group.count).sum::<u32>() == 0 {
+                errors.push(format!("wave {} has no asteroids, it would end straight away", level));
+            }
+            for group in &wave.asteroids {
+                if tiers.find(&group.tier).is_none() {
+                    errors.push(format!("wave {} uses unknown asteroid type \"{}\"", level, group.tier));
+                }
+            }
+            if wave.speed_scale <= 0.0 {
+                errors.push(format!("wave {} speed_scale must be above 0", level));
+            }
+            if wave.saucer_interval < 0.0 {
+                errors.push(format!("wave {} saucer_interval can't be negative", level));
+            }
+            if !(0.0..=1.0).contains(&wave.small_saucer_chance) {
+                errors.push(format!("wave {} small_saucer_chance must be between 0 and 1", level));
+            }
+            if wave.time_limit < 0.0 {
+                errors.push(format!("wave {} time_limit can't be negative", level));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Waves{waves: file.wave})
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    // Loads the wave file if there is one, otherwise uses the built in waves. If those
+    // don't fit a custom asteroids.toml every level is generated instead.
+    pub fn load(path: &str, tiers: &AsteroidTiers) -> Result<Waves, String> {
+        if !Path::new(path).exists() {
+            return match Waves::from_toml(include_str!("../waves.toml"), tiers) {
+                Ok(waves) => Ok(waves),
+                Err(e) => {
+                    println!("Built in waves don't match the asteroid types, generating every level instead:\n{}", e);
+                    Ok(Waves{waves: Vec::new()})
+                }
+            };
+        }
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Waves::from_toml(&contents, tiers).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // The wave for a level (counting from 1), generated if the list has run out
+    pub fn wave(&self, level: u32, tiers: &AsteroidTiers, rng: &mut StdRng) -> Wave {
+        match self.waves.get(level.saturating_sub(1) as usize) {
+            Some(wave) => wave.clone(),
+            None => generate(level, tiers, rng)
+        }
+    }
+}
+
+impl Default for Waves {
+    // The waves shipped with the game
+    fn default() -> Self {
+        Waves::from_toml(include_str!("../waves.toml"), &AsteroidTiers::default()).expect("built in waves.toml is invalid")
+    }
+}
+
+// Another asteroid every third level, picked by spawn_weight, drifting a little faster each level
+// with saucers turning up more often and more of them small
+fn generate(level: u32, tiers: &AsteroidTiers, rng: &mut StdRng) -> Wave {
+    let past_first = level.saturating_sub(1) as f64;
+    let asteroids = (0..(level / 3) + 1)
+        .filter_map(|_| tiers.get(tiers.pick_spawn(rng)))
+        .map(|tier| WaveAsteroids{tier: tier.name.clone(), count: 1})
+        .collect();
+    Wave {
+        asteroids,
+        speed_scale: (1.0 + 0.02 * past_first).min(1.5),
+        saucer_interval: (20.0 - 1.5 * past_first).max(6.0),
+        small_saucer_chance: (0.2 + 0.1 * past_first).min(0.8),
+        time_limit: 0.0
+    }
+}
+
+// Puts the level's asteroids on the screen, away from the ship, and starts its clock
+pub fn spawn_wave(ecs: &mut World, level: u32){
+    let mode = *ecs.read_resource::<GameMode>();
+    let mut ship_position = components::Position{x: (SCREEN_WIDTH/2) as f64, y: (SCREEN_HEIGHT/2) as f64, rot: 0.0};
+    {
+        let players = ecs.read_storage::<components::Player>();
+        let positions = ecs.read_storage::<components::Position>();
+        for (pos, _) in (&positions, &players).join(){
+            ship_position.x = pos.x;
+            ship_position.y = pos.y;
+        }
+    }
+
+    let mut new_asteroids = Vec::<(components::Position, usize)>::new();
+    let wave = {
+        let waves = ecs.read_resource::<Waves>();
+        let tiers = ecs.read_resource::<AsteroidTiers>();
+        let mut game_rng = ecs.write_resource::<crate::GameRng>();
+        let rng = &mut game_rng.rng;
+        let wave = waves.wave(level, &tiers, rng);
+
+        for group in &wave.asteroids {
+            let tier = match tiers.find(&group.tier) {
+                Some(tier) => tier,
+                None => continue
+            };
+            let mut placed = 0;
+            while placed < group.count {
+                let position = components::Position{
+                    x: rng.gen_range(SPAWN_MARGIN..SCREEN_WIDTH as f64 - SPAWN_MARGIN),
+                    y: rng.gen_range(SPAWN_MARGIN..SCREEN_HEIGHT as f64 - SPAWN_MARGIN),
+                    rot: rng.gen_range(0.0..360.0)
+                };
+                let (diff_x, diff_y) = game::position_delta(mode, &position, &ship_position);
+                if diff_x * diff_x + diff_y * diff_y < SPAWN_CLEARANCE * SPAWN_CLEARANCE {
+                    continue;
+                }
+                new_asteroids.push((position, tier));
+                placed += 1;
+            }
+        }
+        wave
+    };
+
+    for (position, tier) in new_asteroids {
+        if let Some(entity) = game::create_asteroid(ecs, position, tier) {
+            if let Some(vel) = ecs.write_storage::<components::Velocity>().get_mut(entity) {
+                vel.x *= wave.speed_scale;
+                vel.y *= wave.speed_scale;
+            }
+        }
+    }
+
+    {
+        //A saucer already counting down keeps its timer unless this wave wants them sooner
+        let mut spawner = ecs.write_resource::<saucer::SaucerSpawner>();
+        if wave.saucer_interval > 0.0 && (spawner.timer <= 0.0 || spawner.timer > wave.saucer_interval) {
+            spawner.timer = wave.saucer_interval;
+        }
+    }
+    let time_left = wave.time_limit;
+    ecs.insert(CurrentWave{wave, time_left});
+}
+
+// Counts down the wave's time limit, true once it has run out
+pub fn timed_out(current: &mut CurrentWave, delta_time: f64) -> bool {
+    if current.wave.time_limit <= 0.0 {
+        return false;
+    }
+    current.time_left -= delta_time;
+    current.time_left <= 0.0
+}
+
+// Seconds left to clear the wave, if it has a time limit
+pub fn time_left(ecs: &World) -> Option<f64> {
+    let current = ecs.read_resource::<CurrentWave>();
+    if current.wave.time_limit > 0.0 {
+        Some(current.time_left.max(0.0))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(contents: &str) -> Result<Waves, String> {
+        Waves::from_toml(contents, &AsteroidTiers::default())
+    }
+
+    #[test]
+    fn built_in_waves_load() {
+        assert!(load(include_str!("../waves.toml")).is_ok());
+    }
+
+    #[test]
+    fn misspelt_wave_field_is_refused() {
+        let error = load("[[wave]]\nasteroids = [{tier = \"large\", count = 2}]\nsaucer_intervall = 10.0\n").unwrap_err();
+        assert!(error.contains("unknown field `saucer_intervall`"), "{}", error);
+    }
+
+    #[test]
+    fn misspelt_asteroid_group_field_is_refused() {
+        let error = load("[[wave]]\nasteroids = [{tier = \"large\", cuont = 2}]\n").unwrap_err();
+        assert!(error.contains("unknown field `cuont`"), "{}", error);
+    }
+
+    #[test]
+    fn misspelt_section_is_refused() {
+        let error = load("[[waves]]\nasteroids = [{tier = \"large\", count = 2}]\n").unwrap_err();
+        assert!(error.contains("unknown field `waves`"), "{}", error);
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let error = load("[[wave]]\nasteroids = [{tier = \"huge\", count = 2}]\n\n[[wave]]\nasteroids = [{tier = \"large\", count = 1}]\nspeed_scale = 0.0\nsmall_saucer_chance = 2.0\n").unwrap_err();
+        assert_eq!(error, "wave 1 uses unknown asteroid type \"huge\"\nwave 2 speed_scale must be above 0\nwave 2 small_saucer_chance must be between 0 and 1");
+    }
+}